@@ -0,0 +1,395 @@
+use encoding::all::ISO_8859_1;
+use encoding::{DecoderTrap, Encoding};
+
+use {decode_param_words, ErrorKind, MailHeader, MailParseError};
+
+/// A single mailbox, e.g. `John Doe <john@example.com>` or just
+/// `john@example.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SingleInfo {
+    /// The display name, if there was one. Any quoting is removed, and
+    /// RFC 2047 encoded-words are decoded.
+    pub display_name: Option<String>,
+    /// The address itself, e.g. `john@example.com`. This is left exactly as
+    /// written (apart from whitespace and comments), so a quoted local part
+    /// keeps its quotes.
+    pub addr: String,
+}
+
+/// A named group of mailboxes, e.g. `Friends: alice@x.com, bob@y.com;`. The
+/// list of mailboxes may be empty, as in `undisclosed-recipients:;`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupInfo {
+    /// The name of the group, decoded in the same way as a display name.
+    pub group_name: String,
+    /// The mailboxes that are part of the group.
+    pub addrs: Vec<SingleInfo>,
+}
+
+/// An entry in an address list, as found in headers such as From, To or Cc.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MailAddr {
+    /// A named group of mailboxes.
+    Group(GroupInfo),
+    /// A single mailbox.
+    Single(SingleInfo),
+}
+
+enum Token<'a> {
+    Atom(&'a str),
+    /// The unescaped contents of a quoted string, and the raw quoted string.
+    Quoted(String, &'a str),
+    Special(char),
+}
+
+struct Lexeme<'a> {
+    token: Token<'a>,
+    offset: usize,
+    space_before: bool,
+}
+
+impl<'a> Lexeme<'a> {
+    fn is_special(&self, c: char) -> bool {
+        matches!(self.token, Token::Special(s) if s == c)
+    }
+
+    /// Whether this token ends the phrase at the start of an entry.
+    fn ends_phrase(&self) -> bool {
+        matches!(self.token, Token::Special(',' | ';' | '<' | ':'))
+    }
+
+    fn push_raw(&self, s: &mut String) {
+        match self.token {
+            Token::Atom(a) => s.push_str(a),
+            Token::Quoted(_, raw) => s.push_str(raw),
+            Token::Special(c) => s.push(c),
+        }
+    }
+}
+
+fn is_special(c: char) -> bool {
+    matches!(c, '(' | ')' | '<' | '>' | ',' | ';' | ':' | '"')
+}
+
+/// Split an address list into atoms, quoted strings and special characters.
+/// Comments are dropped, and like whitespace they separate tokens. An
+/// unterminated comment or quoted string runs to the end of the input.
+fn tokenize(input: &str) -> Vec<Lexeme<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    let mut space_before = false;
+    while let Some((ix, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                space_before = true;
+                continue;
+            }
+            '(' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some((_, '\\')) => {
+                            chars.next();
+                        }
+                        Some((_, '(')) => depth += 1,
+                        Some((_, ')')) => depth -= 1,
+                        Some(_) => (),
+                        None => break,
+                    }
+                }
+                space_before = true;
+                continue;
+            }
+            '"' => {
+                let mut text = String::new();
+                let mut end = input.len();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => {
+                            if let Some((_, c)) = chars.next() {
+                                text.push(c);
+                            }
+                        }
+                        Some((ix_end, '"')) => {
+                            end = ix_end + 1;
+                            break;
+                        }
+                        Some((_, c)) => text.push(c),
+                        None => break,
+                    }
+                }
+                Token::Quoted(text, &input[ix..end])
+            }
+            c if is_special(c) => Token::Special(c),
+            _ => {
+                // An encoded-word is kept in one piece, even if it contains
+                // specials that should have been encoded
+                let word_end = encoded_word_len(&input[ix..]).map_or(ix, |len| ix + len);
+                let mut end = input.len();
+                while let Some(&(ix_next, c)) = chars.peek() {
+                    if ix_next >= word_end && (c.is_whitespace() || is_special(c)) {
+                        end = ix_next;
+                        break;
+                    }
+                    chars.next();
+                }
+                Token::Atom(&input[ix..end])
+            }
+        };
+        tokens.push(Lexeme { token, offset: ix, space_before });
+        space_before = false;
+    }
+    tokens
+}
+
+/// If the input starts with an RFC 2047 encoded-word, return its length.
+fn encoded_word_len(input: &str) -> Option<usize> {
+    let rest = input.strip_prefix("=?")?;
+    let ix_charset_end = rest.find('?')?;
+    let ix_encoding_end = ix_charset_end + 1 + rest[ix_charset_end + 1..].find('?')?;
+    let ix_text_end = ix_encoding_end + 1 + rest[ix_encoding_end + 1..].find("?=")?;
+    if rest[..ix_text_end].contains(char::is_whitespace) {
+        return None;
+    }
+    Some(ix_text_end + 4)
+}
+
+/// Build a display name or group name out of the words of a phrase. Words
+/// that consist of RFC 2047 encoded-words are decoded, and the whitespace
+/// between two adjacent encoded-words is dropped.
+fn phrase(tokens: &[Lexeme]) -> String {
+    let mut result = String::new();
+    let mut prev_encoded = false;
+    for token in tokens {
+        let (text, encoded) = match token.token {
+            Token::Atom(a) => match decode_param_words(a) {
+                Some(decoded) => (decoded, true),
+                None => (a.to_string(), false),
+            },
+            Token::Quoted(ref q, _) => match decode_param_words(q) {
+                Some(decoded) => (decoded, true),
+                None => (q.clone(), false),
+            },
+            Token::Special(c) => (c.to_string(), false),
+        };
+        if token.space_before && !result.is_empty() && !(encoded && prev_encoded) {
+            result.push(' ');
+        }
+        result.push_str(&text);
+        prev_encoded = encoded;
+    }
+    result
+}
+
+/// Parse a single mailbox starting at `pos`, stopping at the separator (or
+/// the end of the input) that follows it. Returns None for an empty entry,
+/// along with the position of the separator.
+fn parse_mailbox(tokens: &[Lexeme], pos: usize) -> Result<(Option<SingleInfo>, usize), MailParseError> {
+    let invalid = |ix| MailParseError::Generic(ErrorKind::InvalidAddress, ix);
+    let end = tokens[pos..]
+        .iter()
+        .position(Lexeme::ends_phrase)
+        .map_or(tokens.len(), |ix| pos + ix);
+    match tokens.get(end) {
+        Some(open) if open.is_special('<') => {
+            let mut addr = String::new();
+            let mut ix = end + 1;
+            loop {
+                match tokens.get(ix) {
+                    Some(token) if token.is_special('>') => break,
+                    Some(token) => token.push_raw(&mut addr),
+                    None => {
+                        return Err(MailParseError::Generic(ErrorKind::UnterminatedAddress, open.offset))
+                    }
+                }
+                ix += 1;
+            }
+            // Anything after the closing angle bracket is ignored
+            while tokens.get(ix).is_some_and(|t| !t.is_special(',') && !t.is_special(';')) {
+                ix += 1;
+            }
+            let display_name = Some(phrase(&tokens[pos..end])).filter(|name| !name.is_empty());
+            Ok((Some(SingleInfo { display_name, addr }), ix))
+        }
+        Some(colon) if colon.is_special(':') => Err(invalid(colon.offset)),
+        _ if pos == end => Ok((None, end)),
+        _ => {
+            let mut addr = String::new();
+            for (ix, token) in tokens[pos..end].iter().enumerate() {
+                if let Token::Special(_) = token.token {
+                    return Err(invalid(token.offset));
+                } else if ix > 0 && token.space_before {
+                    return Err(invalid(tokens[pos].offset));
+                }
+                token.push_raw(&mut addr);
+            }
+            Ok((Some(SingleInfo { display_name: None, addr }), end))
+        }
+    }
+}
+
+/// Parse an address list, such as the value of a From, To or Cc header, into
+/// its mailboxes and groups. Comments are ignored, display names are
+/// unquoted and have any RFC 2047 encoded-words decoded, and empty entries
+/// in the list are skipped. Besides commas, semicolons are also accepted as
+/// separators between the entries, as some mail clients use them.
+///
+/// An `UnterminatedAddress` error is returned if an address is missing its
+/// closing angle bracket, and an `InvalidAddress` error if an entry is
+/// neither a bare address nor a display name followed by an address in
+/// angle brackets. The offset of the error is the byte offset into
+/// `addrs`.
+///
+/// # Examples
+/// ```
+///     use mailparse::{addrparse, MailAddr, SingleInfo};
+///     let addrs = addrparse("John Doe <john@example.com>, \"Doe, Jane\" <jane@example.com>").unwrap();
+///     assert_eq!(addrs[0], MailAddr::Single(SingleInfo {
+///         display_name: Some("John Doe".to_string()),
+///         addr: "john@example.com".to_string(),
+///     }));
+///     match addrs[1] {
+///         MailAddr::Single(ref info) => {
+///             assert_eq!(info.display_name, Some("Doe, Jane".to_string()));
+///         }
+///         _ => panic!(),
+///     }
+///
+///     let addrs = addrparse("Team: alice@example.com, =?utf-8?Q?B=C3=B6b?= <bob@example.com>;").unwrap();
+///     match addrs[0] {
+///         MailAddr::Group(ref group) => {
+///             assert_eq!(group.group_name, "Team");
+///             assert_eq!(group.addrs[1].display_name, Some("B\u{f6}b".to_string()));
+///         }
+///         _ => panic!(),
+///     }
+/// ```
+pub fn addrparse(addrs: &str) -> Result<Vec<MailAddr>, MailParseError> {
+    let tokens = tokenize(addrs);
+    let mut result = Vec::new();
+    let mut pos = 0;
+    while pos < tokens.len() {
+        let group_colon = tokens[pos..]
+            .iter()
+            .position(Lexeme::ends_phrase)
+            .map(|ix| pos + ix)
+            .filter(|&ix| tokens[ix].is_special(':'));
+        if let Some(colon) = group_colon {
+            let group_name = phrase(&tokens[pos..colon]);
+            let mut addrs = Vec::new();
+            pos = colon + 1;
+            loop {
+                let (single, next) = parse_mailbox(&tokens, pos)?;
+                addrs.extend(single);
+                pos = next + 1;
+                if next >= tokens.len() || tokens[next].is_special(';') {
+                    break;
+                }
+            }
+            result.push(MailAddr::Group(GroupInfo { group_name, addrs }));
+            if pos < tokens.len() && tokens[pos].is_special(',') {
+                pos += 1;
+            }
+        } else {
+            let (single, next) = parse_mailbox(&tokens, pos)?;
+            result.extend(single.map(MailAddr::Single));
+            pos = next + 1;
+        }
+    }
+    Ok(result)
+}
+
+/// Parse the value of an address header, as with `addrparse`. Unlike running
+/// `addrparse` on the result of `MailHeader::get_value`, this only decodes
+/// the encoded-words in display names and group names after the address list
+/// has been split up, so a decoded name that contains a comma or angle
+/// brackets does not change how the list is parsed.
+///
+/// # Examples
+/// ```
+///     use mailparse::{addrparse_header, parse_header, MailAddr};
+///     let (header, _) = parse_header(b"To: =?utf-8?Q?Doe,_John?= <john@example.com>").unwrap();
+///     let addrs = addrparse_header(&header).unwrap();
+///     assert_eq!(addrs.len(), 1);
+///     match addrs[0] {
+///         MailAddr::Single(ref info) => {
+///             assert_eq!(info.display_name, Some("Doe, John".to_string()));
+///             assert_eq!(info.addr, "john@example.com");
+///         }
+///         _ => panic!(),
+///     }
+/// ```
+pub fn addrparse_header(header: &MailHeader) -> Result<Vec<MailAddr>, MailParseError> {
+    let value = ISO_8859_1.decode(header.value, DecoderTrap::Replace)?;
+    addrparse(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single(display_name: Option<&str>, addr: &str) -> SingleInfo {
+        SingleInfo {
+            display_name: display_name.map(String::from),
+            addr: addr.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_addresses() {
+        assert_eq!(addrparse("").unwrap(), vec![]);
+        assert_eq!(addrparse(" , ,").unwrap(), vec![]);
+        assert_eq!(addrparse("john@x.com").unwrap(),
+                   vec![MailAddr::Single(single(None, "john@x.com"))]);
+        assert_eq!(addrparse("<john@x.com>").unwrap(),
+                   vec![MailAddr::Single(single(None, "john@x.com"))]);
+        assert_eq!(addrparse("John  Q.\r\n Doe <john@x.com> (work), jane@y.com (Jane)").unwrap(),
+                   vec![MailAddr::Single(single(Some("John Q. Doe"), "john@x.com")),
+                        MailAddr::Single(single(None, "jane@y.com"))]);
+        assert_eq!(addrparse("\"Doe, \\\"J\\\"\" < john @ x.com >; \"q d\"@y.com").unwrap(),
+                   vec![MailAddr::Single(single(Some("Doe, \"J\""), "john@x.com")),
+                        MailAddr::Single(single(None, "\"q d\"@y.com"))]);
+        assert_eq!(addrparse("\"\" <a@x>, Null <>").unwrap(),
+                   vec![MailAddr::Single(single(None, "a@x")),
+                        MailAddr::Single(single(Some("Null"), ""))]);
+
+        // Encoded-words in display names, which may be adjacent to specials
+        assert_eq!(addrparse("=?utf-8?Q?J=C3=B6rg?= =?utf-8?Q?_M?=<j@x>, \"=?utf-8?B?SsO2cmc=?=\" <k@x>").unwrap(),
+                   vec![MailAddr::Single(single(Some("J\u{f6}rg M"), "j@x")),
+                        MailAddr::Single(single(Some("J\u{f6}rg"), "k@x"))]);
+        assert_eq!(addrparse("=?utf-8?Q?Doe,_<J>?= <j@x>, =?x?q <k@x>").unwrap(),
+                   vec![MailAddr::Single(single(Some("Doe, <J>"), "j@x")),
+                        MailAddr::Single(single(Some("=?x?q"), "k@x"))]);
+        assert_eq!(addrparse("a@x,=?utf-8?Q?B=C3=B6b?=(c)<b@x>,=?utf-8?Q?T?=:;").unwrap(),
+                   vec![MailAddr::Single(single(None, "a@x")),
+                        MailAddr::Single(single(Some("B\u{f6}b"), "b@x")),
+                        MailAddr::Group(GroupInfo { group_name: "T".to_string(), addrs: vec![] })]);
+
+        // Groups
+        assert_eq!(addrparse("undisclosed-recipients:;").unwrap(),
+                   vec![MailAddr::Group(GroupInfo { group_name: "undisclosed-recipients".to_string(),
+                                                    addrs: vec![] })]);
+        assert_eq!(addrparse("a@x, The Team: b@x, Bob <c@x>; , d@x").unwrap(),
+                   vec![MailAddr::Single(single(None, "a@x")),
+                        MailAddr::Group(GroupInfo { group_name: "The Team".to_string(),
+                                                    addrs: vec![single(None, "b@x"),
+                                                                single(Some("Bob"), "c@x")] }),
+                        MailAddr::Single(single(None, "d@x"))]);
+        assert_eq!(addrparse("Open: a@x").unwrap(),
+                   vec![MailAddr::Group(GroupInfo { group_name: "Open".to_string(),
+                                                    addrs: vec![single(None, "a@x")] })]);
+
+        let check_err = |input, expected_kind, expected_ix| match addrparse(input).unwrap_err() {
+            MailParseError::Generic(kind, ix) => {
+                assert_eq!(kind, expected_kind);
+                assert_eq!(ix, expected_ix);
+            }
+            e => panic!("Unexpected error {:?}", e),
+        };
+        check_err("a@x, John <john@x", ErrorKind::UnterminatedAddress, 10);
+        check_err("a@x, John Doe", ErrorKind::InvalidAddress, 5);
+        check_err("john@x>", ErrorKind::InvalidAddress, 6);
+        check_err("A: B: c@x;", ErrorKind::InvalidAddress, 4);
+        check_err("A: c@x; d@x e@x", ErrorKind::InvalidAddress, 8);
+    }
+}
@@ -0,0 +1,337 @@
+use {ErrorKind, MailParseError};
+
+/// The results of the message authentication checks recorded in an
+/// Authentication-Results header, as returned by `parse_authentication_results`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthResults {
+    /// The identifier of the server that performed the checks, e.g.
+    /// "mx.example.com".
+    pub authserv_id: String,
+    /// The result of each check, in the order in which they appear. This is
+    /// empty if the header says that no checks were performed.
+    pub results: Vec<AuthResult>,
+}
+
+/// The result of a single authentication method, e.g. `dkim=pass`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthResult {
+    /// The (lowercased) method, e.g. "spf", "dkim" or "dmarc", without any
+    /// version number.
+    pub method: String,
+    /// The (lowercased) result of the method, e.g. "pass" or "fail".
+    pub result: String,
+    /// The reason given for the result, if any.
+    pub reason: Option<String>,
+    /// The properties of the message that the method checked, e.g.
+    /// `header.from=example.com`.
+    pub properties: Vec<AuthProperty>,
+}
+
+/// A property that an authentication method checked, e.g.
+/// `smtp.mailfrom=alice@example.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthProperty {
+    /// The (lowercased) type of the property, e.g. "smtp" or "header".
+    pub ptype: String,
+    /// The (lowercased) name of the property, e.g. "mailfrom" or "from".
+    pub property: String,
+    /// The value of the property, without any quotes.
+    pub value: String,
+}
+
+impl AuthResult {
+    /// Get the value of the first property with the given name, written as
+    /// `ptype.property` (e.g. "header.from"). The name is matched
+    /// case-insensitively.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_authentication_results;
+    ///     let results = parse_authentication_results(
+    ///             "mx.example.com; dmarc=pass header.from=example.com")
+    ///         .unwrap();
+    ///     assert_eq!(results.results[0].property("Header.From"), Some("example.com"));
+    ///     assert_eq!(results.results[0].property("smtp.mailfrom"), None);
+    /// ```
+    pub fn property(&self, name: &str) -> Option<&str> {
+        let (ptype, property) = name.split_once('.')?;
+        self.properties
+            .iter()
+            .find(|p| {
+                p.ptype.eq_ignore_ascii_case(ptype) && p.property.eq_ignore_ascii_case(property)
+            })
+            .map(|p| &p.value[..])
+    }
+}
+
+/// Parse the value of an Authentication-Results header, as described in RFC
+/// 8601, into the identifier of the server that added it and the result of
+/// each authentication method. Comments are skipped, quoted values are
+/// unquoted, and a version number after the server identifier or a method is
+/// ignored. Words in a method's result that are not a `reason` or a
+/// `ptype.property=value` pair are skipped as well.
+///
+/// An `InvalidAuthResults` error is returned if the server identifier is
+/// missing, or if a method is not followed by `=` and a result. The offset
+/// of the error is the byte offset into `value`.
+///
+/// # Examples
+/// ```
+///     use mailparse::parse_authentication_results;
+///     let results = parse_authentication_results(concat!(
+///             "mx.example.org;\n",
+///             "\tspf=pass (sender is permitted) smtp.mailfrom=alice@example.com;\n",
+///             "\tdkim=fail reason=\"bad signature\" header.d=example.com header.s=sel;\n",
+///             "\tdmarc=pass header.from=example.com"))
+///         .unwrap();
+///     assert_eq!(results.authserv_id, "mx.example.org");
+///     assert_eq!(results.results.len(), 3);
+///     assert_eq!(results.results[0].method, "spf");
+///     assert_eq!(results.results[0].result, "pass");
+///     assert_eq!(results.results[0].property("smtp.mailfrom"), Some("alice@example.com"));
+///     assert_eq!(results.results[1].result, "fail");
+///     assert_eq!(results.results[1].reason, Some("bad signature".to_string()));
+///     assert_eq!(results.results[1].property("header.s"), Some("sel"));
+///     assert_eq!(results.results[2].method, "dmarc");
+/// ```
+pub fn parse_authentication_results(value: &str) -> Result<AuthResults, MailParseError> {
+    let invalid = |ix| MailParseError::Generic(ErrorKind::InvalidAuthResults, ix);
+    let tokens = tokenize(value);
+    let authserv_id = match tokens.first() {
+        Some(Lexeme { token: Token::Word(id), .. }) => id.clone(),
+        Some(lexeme) => return Err(invalid(lexeme.offset)),
+        None => return Err(invalid(value.len())),
+    };
+    let mut results = AuthResults { authserv_id, results: Vec::new() };
+
+    // Each result follows a semicolon, and anything else before the first
+    // one (i.e. the version) is skipped
+    let mut pos = 1;
+    while pos < tokens.len() {
+        if !tokens[pos].is_special(';') {
+            pos += 1;
+            continue;
+        }
+        pos += 1;
+        let (method, offset) = match tokens.get(pos) {
+            Some(Lexeme { token: Token::Word(method), offset, .. }) => (method, *offset),
+            Some(lexeme) if lexeme.is_special(';') => continue,
+            Some(lexeme) => return Err(invalid(lexeme.offset)),
+            None => break,
+        };
+        let is_last = pos + 1 >= tokens.len() || tokens[pos + 1].is_special(';');
+        if method.eq_ignore_ascii_case("none") && is_last {
+            pos += 1;
+            continue;
+        }
+        let (result, next) = match value_at(&tokens, pos + 1) {
+            Some(v) => v,
+            None => return Err(invalid(offset)),
+        };
+        let method = method.split('/').next().unwrap_or("");
+        let mut auth_result = AuthResult {
+            method: method.to_ascii_lowercase(),
+            result: result.to_ascii_lowercase(),
+            ..Default::default()
+        };
+        pos = next;
+        while let Some(lexeme) = tokens.get(pos) {
+            let name = match lexeme.token {
+                Token::Word(ref name) => name,
+                Token::Special(';') => break,
+                _ => {
+                    pos += 1;
+                    continue;
+                }
+            };
+            let (value, next) = match value_at(&tokens, pos + 1) {
+                Some(v) => v,
+                None => {
+                    pos += 1;
+                    continue;
+                }
+            };
+            if name.eq_ignore_ascii_case("reason") {
+                auth_result.reason.get_or_insert(value);
+            } else if let Some((ptype, property)) = name.split_once('.') {
+                auth_result.properties.push(AuthProperty {
+                    ptype: ptype.to_ascii_lowercase(),
+                    property: property.to_ascii_lowercase(),
+                    value,
+                });
+            }
+            pos = next;
+        }
+        results.results.push(auth_result);
+    }
+    Ok(results)
+}
+
+/// If the tokens at `pos` are `=` followed by a value, return the value and
+/// the position after it. Tokens that directly follow the value without
+/// whitespace in between are part of it, so that e.g. the base64 padding in
+/// `header.b=abc=` is kept.
+fn value_at(tokens: &[Lexeme], pos: usize) -> Option<(String, usize)> {
+    if !tokens.get(pos)?.is_special('=') {
+        return None;
+    }
+    let mut value = match tokens.get(pos + 1)?.token {
+        Token::Word(ref v) | Token::Quoted(ref v) => v.clone(),
+        Token::Special(_) => return None,
+    };
+    let mut pos = pos + 2;
+    while let Some(lexeme) = tokens.get(pos) {
+        match lexeme.token {
+            _ if lexeme.space_before => break,
+            Token::Special(';') => break,
+            Token::Word(ref v) | Token::Quoted(ref v) => value.push_str(v),
+            Token::Special(c) => value.push(c),
+        }
+        pos += 1;
+    }
+    Some((value, pos))
+}
+
+enum Token {
+    Word(String),
+    /// The unescaped contents of a quoted string.
+    Quoted(String),
+    Special(char),
+}
+
+struct Lexeme {
+    token: Token,
+    offset: usize,
+    space_before: bool,
+}
+
+impl Lexeme {
+    fn is_special(&self, c: char) -> bool {
+        matches!(self.token, Token::Special(s) if s == c)
+    }
+}
+
+/// Split the value of an Authentication-Results header into words, quoted
+/// strings and the `;` and `=` separators. Comments are dropped, and like
+/// whitespace they separate tokens. An unterminated comment or quoted string
+/// runs to the end of the input.
+fn tokenize(value: &str) -> Vec<Lexeme> {
+    let mut tokens = Vec::new();
+    let mut chars = value.char_indices().peekable();
+    let mut space_before = false;
+    while let Some((ix, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                space_before = true;
+                continue;
+            }
+            '(' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some((_, '\\')) => {
+                            chars.next();
+                        }
+                        Some((_, '(')) => depth += 1,
+                        Some((_, ')')) => depth -= 1,
+                        Some(_) => (),
+                        None => break,
+                    }
+                }
+                space_before = true;
+                continue;
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => text.extend(chars.next().map(|(_, c)| c)),
+                        Some((_, '"')) | None => break,
+                        Some((_, c)) => text.push(c),
+                    }
+                }
+                Token::Quoted(text)
+            }
+            ';' | '=' => Token::Special(c),
+            c => {
+                let mut word = c.to_string();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | '"' | ';' | '=') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push(Lexeme { token, offset: ix, space_before });
+        space_before = false;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property(ptype: &str, property: &str, value: &str) -> AuthProperty {
+        AuthProperty {
+            ptype: ptype.to_string(),
+            property: property.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_auth_results() {
+        let results = parse_authentication_results("example.com; none").unwrap();
+        assert_eq!(results, AuthResults { authserv_id: "example.com".to_string(), results: vec![] });
+        let results = parse_authentication_results("example.com 1 (v1);").unwrap();
+        assert_eq!(results.authserv_id, "example.com");
+        assert!(results.results.is_empty());
+
+        let results = parse_authentication_results(concat!(
+            "mx.example.net (comment; with = specials);\r\n",
+            " DKIM/1 = Pass (good) header.i=@example.com HEADER.B=\"ab c\" header.b=xyz+/==;",
+            " ;dmarc=fail reason=policy reason=ignored policy.dmarc=reject stray;",
+            " arc=none")).unwrap();
+        assert_eq!(results.authserv_id, "mx.example.net");
+        assert_eq!(results.results, vec![
+            AuthResult {
+                method: "dkim".to_string(),
+                result: "pass".to_string(),
+                reason: None,
+                properties: vec![property("header", "i", "@example.com"),
+                                 property("header", "b", "ab c"),
+                                 property("header", "b", "xyz+/==")],
+            },
+            AuthResult {
+                method: "dmarc".to_string(),
+                result: "fail".to_string(),
+                reason: Some("policy".to_string()),
+                properties: vec![property("policy", "dmarc", "reject")],
+            },
+            AuthResult {
+                method: "arc".to_string(),
+                result: "none".to_string(),
+                reason: None,
+                properties: vec![],
+            },
+        ]);
+        assert_eq!(results.results[0].property("header.b"), Some("ab c"));
+        assert_eq!(results.results[0].property("header"), None);
+
+        let check_err = |input, expected_ix| match parse_authentication_results(input).unwrap_err() {
+            MailParseError::Generic(ErrorKind::InvalidAuthResults, ix) => assert_eq!(ix, expected_ix),
+            err => panic!("Unexpected error {:?}", err),
+        };
+        check_err("", 0);
+        check_err(" (only a comment)", 17);
+        check_err("; spf=pass", 0);
+        check_err("example.com; spf", 13);
+        check_err("example.com; spf pass", 13);
+        check_err("example.com; spf=;", 13);
+        check_err("example.com; =pass", 13);
+    }
+}
@@ -0,0 +1,170 @@
+use encoding::label::encoding_from_whatwg_label;
+use encoding::EncodingRef;
+
+/// Charset labels seen in real-world mail that are not recognized by
+/// `encoding_from_whatwg_label`, along with a label for the same charset that
+/// it does recognize. The aliases are lowercase. This table can't be changed;
+/// use `charset_encoding_with_aliases` to look up labels with aliases of your
+/// own on top of these. An alias can only name a charset that the `encoding`
+/// crate supports, so labels for e.g. the DOS code page CP850 (`cp-850` or
+/// `ibm850`) are still unknown.
+pub const CHARSET_ALIASES: &[(&str, &str)] = &[
+    // ASCII
+    ("ansi_x3.4-1986", "us-ascii"),
+    ("iso_646.irv:1991", "us-ascii"),
+    ("iso646-us", "us-ascii"),
+    ("us", "us-ascii"),
+    ("ibm367", "us-ascii"),
+    ("cp367", "us-ascii"),
+    ("csascii", "us-ascii"),
+    // UTF-8
+    ("unicode-2-0-utf-8", "utf-8"),
+    ("x-unicode20utf8", "utf-8"),
+    ("utf8mb4", "utf-8"),
+    // Latin
+    ("latin-1", "iso-8859-1"),
+    ("latin-2", "iso-8859-2"),
+    ("latin-9", "iso-8859-15"),
+    ("8859-1", "iso-8859-1"),
+    ("cp-1252", "windows-1252"),
+    ("win-1252", "windows-1252"),
+    ("windows1252", "windows-1252"),
+    ("ms-ansi", "windows-1252"),
+    ("cp-1250", "windows-1250"),
+    ("cp-1251", "windows-1251"),
+    ("macroman", "macintosh"),
+    ("koi8r", "koi8-r"),
+    ("tis620", "tis-620"),
+    // Chinese
+    ("gb-2312", "gb2312"),
+    ("csgb231280", "gb2312"),
+    ("euc-cn", "gb2312"),
+    ("x-euc-cn", "gb2312"),
+    ("cn-gb", "gb2312"),
+    ("big-5", "big5"),
+    ("big5hkscs", "big5-hkscs"),
+    ("x-big5", "big5"),
+    // Korean
+    ("ks_c_5601", "euc-kr"),
+    ("uhc", "euc-kr"),
+    ("x-windows-949", "euc-kr"),
+    // Microsoft code pages for CJK charsets
+    ("cp932", "windows-31j"),
+    ("ms932", "windows-31j"),
+    ("windows-932", "windows-31j"),
+    ("cp936", "gbk"),
+    ("ms936", "gbk"),
+    ("windows-936", "gbk"),
+    ("cp949", "windows-949"),
+    ("ms949", "windows-949"),
+    ("cp-949", "windows-949"),
+    ("cp-936", "gbk"),
+    ("cp950", "big5"),
+    ("cp-950", "big5"),
+    ("ms950", "big5"),
+    ("windows-950", "big5"),
+];
+
+/// Look up the encoding for a charset label, as found in a Content-Type header
+/// or an encoded-word. This accepts everything `encoding_from_whatwg_label`
+/// does, as well as the aliases in `CHARSET_ALIASES` and labels that only
+/// differ from a known one by using underscores in place of hyphens (e.g.
+/// `iso8859_15` or `euc_kr`). This is the lookup used by the rest of the
+/// crate. Labels are matched case-insensitively and
+/// surrounding whitespace is ignored, so the charset can be passed exactly
+/// as it appears in the message (e.g. `Shift_JIS`).
+///
+/// # Examples
+/// ```
+///     use mailparse::charset_encoding;
+///     assert_eq!(charset_encoding("UTF-8").unwrap().name(), "utf-8");
+///     assert_eq!(charset_encoding("cp-1252").unwrap().name(), "windows-1252");
+///     assert_eq!(charset_encoding("iso8859_15").unwrap().name(), "iso-8859-15");
+///     assert_eq!(charset_encoding("Shift_JIS").unwrap().name(), "windows-31j");
+///     assert!(charset_encoding("x-no-such-charset").is_none());
+/// ```
+pub fn charset_encoding(label: &str) -> Option<EncodingRef> {
+    charset_encoding_with_aliases(label, &[])
+}
+
+/// Look up the encoding for a charset label as with `charset_encoding`, but
+/// also accept the given aliases. Like the entries in `CHARSET_ALIASES`, each
+/// alias is paired with a label that `encoding_from_whatwg_label` recognizes,
+/// and is matched case-insensitively. The extra aliases are checked before
+/// the built-in ones, so they can also be used to override those. As the
+/// aliases have to map to a supported charset, this can't add support for
+/// charsets that the `encoding` crate lacks, such as CP850.
+///
+/// # Examples
+/// ```
+///     use mailparse::charset_encoding_with_aliases;
+///     let aliases = [("cp-866", "ibm866")];
+///     assert_eq!(charset_encoding_with_aliases("CP-866", &aliases).unwrap().name(), "ibm866");
+///     assert_eq!(charset_encoding_with_aliases("latin-9", &aliases).unwrap().name(), "iso-8859-15");
+///     assert!(charset_encoding_with_aliases("x-no-such-charset", &aliases).is_none());
+/// ```
+pub fn charset_encoding_with_aliases(label: &str, aliases: &[(&str, &str)]) -> Option<EncodingRef> {
+    if let Some(encoding) = encoding_from_whatwg_label(label) {
+        return Some(encoding);
+    }
+    let label = label.trim().to_ascii_lowercase();
+    let canonical = aliases
+        .iter()
+        .chain(CHARSET_ALIASES)
+        .find(|&&(alias, _)| alias.eq_ignore_ascii_case(&label));
+    if let Some(&(_, canonical)) = canonical {
+        return encoding_from_whatwg_label(canonical);
+    }
+    if label.contains('_') {
+        return encoding_from_whatwg_label(&label.replace('_', "-"));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_aliases() {
+        assert_eq!(charset_encoding("utf-8").unwrap().name(), "utf-8");
+        assert_eq!(charset_encoding("unicode-1-1-utf-8").unwrap().name(), "utf-8");
+        assert_eq!(charset_encoding(" Unicode-2-0-UTF-8 ").unwrap().name(), "utf-8");
+        assert_eq!(charset_encoding("ansi_x3.4-1968").unwrap().name(), "windows-1252");
+        assert_eq!(charset_encoding("ISO646-US").unwrap().name(), "windows-1252");
+        assert_eq!(charset_encoding("latin-1").unwrap().name(), "windows-1252");
+        assert_eq!(charset_encoding("win-1252").unwrap().name(), "windows-1252");
+        assert_eq!(charset_encoding("cp932").unwrap().name(), "windows-31j");
+        assert_eq!(charset_encoding("euc_kr").unwrap().name(), "windows-949");
+        assert_eq!(charset_encoding("shift_jis").unwrap().name(), "windows-31j");
+        assert_eq!(charset_encoding("csGB2312").unwrap().name(), "gbk");
+        assert_eq!(charset_encoding("GB_2312-80").unwrap().name(), "gbk");
+        assert_eq!(charset_encoding("EUC-CN").unwrap().name(), "gbk");
+        assert_eq!(charset_encoding("Big-5").unwrap().name(), "big5-2003");
+        assert_eq!(charset_encoding("big5_hkscs").unwrap().name(), "big5-2003");
+        assert_eq!(charset_encoding("KS_C_5601").unwrap().name(), "windows-949");
+        assert!(charset_encoding("cp-850").is_none());
+        assert!(charset_encoding("").is_none());
+        assert!(charset_encoding("no_such_charset").is_none());
+
+        // Every alias must map to a label that is actually recognized
+        for &(alias, canonical) in CHARSET_ALIASES {
+            assert_eq!(alias, alias.to_ascii_lowercase());
+            assert!(encoding_from_whatwg_label(alias).is_none(), "{} is redundant", alias);
+            assert!(encoding_from_whatwg_label(canonical).is_some(), "{} is unknown", canonical);
+        }
+    }
+
+    #[test]
+    fn lookup_extra_aliases() {
+        let aliases = [("Cp-866", "ibm866"), ("mac-roman", "macintosh")];
+        assert_eq!(charset_encoding_with_aliases(" CP-866 ", &aliases).unwrap().name(), "ibm866");
+        assert_eq!(charset_encoding_with_aliases("Mac-Roman", &aliases).unwrap().name(), "mac-roman");
+        assert!(charset_encoding("cp-866").is_none());
+        assert!(charset_encoding("mac-roman").is_none());
+        assert_eq!(charset_encoding_with_aliases("win-1252", &aliases).unwrap().name(), "windows-1252");
+        assert_eq!(charset_encoding_with_aliases("utf-8", &aliases).unwrap().name(), "utf-8");
+        // The encoding crate has no CP850, so there is nothing to alias it to
+        assert!(charset_encoding_with_aliases("cp-850", &aliases).is_none());
+    }
+}
@@ -0,0 +1,63 @@
+/// Undo the soft line breaks in a `format=flowed` text body, as described in
+/// RFC 3676. Lines ending in a space are joined with the following line, as
+/// long as that line has the same quote depth and isn't a signature separator
+/// (`-- `). Space-stuffing is removed, and if `delsp` is set the trailing
+/// space of each flowed line is removed as well. Quoted paragraphs are output
+/// with their `>` markers followed by a single space. The resulting lines are
+/// terminated by LF rather than CRLF.
+pub fn unflow(text: &str, delsp: bool) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    // The quote depth of the last output line, if it is still open because
+    // the input line that ended it was flowed.
+    let mut open_depth = None;
+    for line in text.lines() {
+        let depth = line.bytes().take_while(|c| *c == b'>').count();
+        let mut content = &line[depth..];
+        if content.starts_with(' ') {
+            content = &content[1..];
+        }
+        let is_signature = content == "-- ";
+        let is_flowed = content.ends_with(' ') && !is_signature;
+        if is_flowed && delsp {
+            content = &content[..content.len() - 1];
+        }
+        match lines.last_mut() {
+            Some(last) if open_depth == Some(depth) && !is_signature => last.push_str(content),
+            _ => {
+                let mut new_line = ">".repeat(depth);
+                if depth > 0 {
+                    new_line.push(' ');
+                }
+                new_line.push_str(content);
+                lines.push(new_line);
+            }
+        }
+        open_depth = if is_flowed { Some(depth) } else { None };
+    }
+    let mut result = lines.join("\n");
+    if text.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unflow_text() {
+        assert_eq!(unflow("", false), "");
+        assert_eq!(unflow("fixed\r\nlines\r\n", false), "fixed\nlines\n");
+        assert_eq!(unflow("one \r\ntwo \r\nthree\r\nfour", false), "one two three\nfour");
+        assert_eq!(unflow("one \r\ntwo\r\n", true), "onetwo\n");
+        // Space-stuffed lines
+        assert_eq!(unflow(" From here \r\n >not quoted\r\n", false), "From here >not quoted\n");
+        // Quoted paragraphs are only joined with lines of the same depth
+        assert_eq!(unflow("> a \r\n> b\r\n>> c \r\n>>d \r\ne\r\n", false),
+                   "> a b\n>> c d \ne\n");
+        // The signature separator is never flowed and is never joined onto
+        assert_eq!(unflow("text \r\n-- \r\nsig\r\n", false), "text \n-- \nsig\n");
+        assert_eq!(unflow("-- \r\n", true), "-- \n");
+    }
+}
@@ -0,0 +1,169 @@
+/// Elements that start a new line in the text, besides `br` and `p`.
+const LINE_BREAK_ELEMENTS: &[&str] = &[
+    "div", "li", "tr", "hr", "ul", "ol", "table", "blockquote", "pre", "h1", "h2", "h3", "h4",
+    "h5", "h6",
+];
+
+/// Elements whose contents are not text to be shown.
+const HIDDEN_ELEMENTS: &[&str] = &["head", "script", "style", "title"];
+
+/// Convert HTML to plain text, for e.g. a preview of a message that only has
+/// an HTML body. This is not a full HTML parser: tags and comments are
+/// dropped (along with the contents of elements such as `script` and
+/// `style`), runs of whitespace are collapsed to a single space, and the
+/// common named character references as well as numeric ones are decoded.
+/// Line breaks are inserted for `br` and block elements such as `div` or
+/// `li`, and paragraphs are separated by a blank line.
+///
+/// # Examples
+/// ```
+///     use mailparse::html_to_text;
+///     let text = html_to_text("<html><body><p>Hello,\n  <b>world</b>!</p><p>Fish &amp; chips<br>&#8364;5</p></body></html>");
+///     assert_eq!(text, "Hello, world!\n\nFish & chips\n\u{20ac}5");
+/// ```
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(c) = rest.chars().next() {
+        if c == '<' && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || "/!?".contains(c)) {
+            if rest.starts_with("<!--") {
+                rest = rest.find("-->").map_or("", |ix| &rest[ix + 3..]);
+                continue;
+            }
+            let ix_end = find_tag_end(rest);
+            let tag = &rest[1..ix_end];
+            rest = &rest[ix_end..];
+            let closing = tag.starts_with('/');
+            let name = tag
+                .trim_start_matches('/')
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .next()
+                .unwrap_or("")
+                .to_ascii_lowercase();
+            if HIDDEN_ELEMENTS.contains(&name.as_str()) && !closing && !tag.ends_with("/>") {
+                let end_tag = format!("</{}", name);
+                rest = rest
+                    .to_ascii_lowercase()
+                    .find(&end_tag)
+                    .map_or("", |ix| &rest[ix + end_tag.len()..]);
+                rest = rest.find('>').map_or("", |ix| &rest[ix + 1..]);
+            } else if name == "p" {
+                push_line_break(&mut text, true);
+            } else if name == "br" && !text.is_empty() {
+                text.truncate(text.trim_end_matches(' ').len());
+                text.push('\n');
+            } else if LINE_BREAK_ELEMENTS.contains(&name.as_str()) {
+                push_line_break(&mut text, false);
+            }
+        } else if c == '&' {
+            let (decoded, len) = decode_char_ref(rest).unwrap_or(('&', 1));
+            text.push(decoded);
+            rest = &rest[len..];
+        } else if c.is_whitespace() {
+            if !text.is_empty() && !text.ends_with(' ') && !text.ends_with('\n') {
+                text.push(' ');
+            }
+            rest = &rest[c.len_utf8()..];
+        } else {
+            text.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    text.truncate(text.trim_end().len());
+    text
+}
+
+/// Find the end of the tag at the start of the HTML, i.e. the offset just
+/// past its `>`. A `>` inside a quoted attribute value doesn't end the tag. An
+/// unterminated tag runs to the end of the HTML.
+fn find_tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (ix, c) in html.bytes().enumerate() {
+        match (quote, c) {
+            (None, b'>') => return ix + 1,
+            (None, b'"') | (None, b'\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            _ => (),
+        }
+    }
+    html.len()
+}
+
+/// Start a new line (or a new paragraph, after a blank line), unless the text
+/// is empty or already ends in one.
+fn push_line_break(text: &mut String, blank_line: bool) {
+    text.truncate(text.trim_end_matches(' ').len());
+    if text.is_empty() {
+        return;
+    }
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    if blank_line && !text.ends_with("\n\n") {
+        text.push('\n');
+    }
+}
+
+/// Decode the character reference at the start of the input, returning the
+/// character and the length of the reference.
+fn decode_char_ref(input: &str) -> Option<(char, usize)> {
+    let ix_end = input.get(1..12).unwrap_or(&input[1..]).find(';')? + 1;
+    let name = &input[1..ix_end];
+    let c = if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(|c| c == 'x' || c == 'X') {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        std::char::from_u32(code)?
+    } else {
+        match name {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            "nbsp" => '\u{a0}',
+            "copy" => '\u{a9}',
+            "reg" => '\u{ae}',
+            "hellip" => '\u{2026}',
+            "ndash" => '\u{2013}',
+            "mdash" => '\u{2014}',
+            "lsquo" => '\u{2018}',
+            "rsquo" => '\u{2019}',
+            "ldquo" => '\u{201c}',
+            "rdquo" => '\u{201d}',
+            "euro" => '\u{20ac}',
+            _ => return None,
+        }
+    };
+    Some((c, ix_end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_html() {
+        assert_eq!(html_to_text(""), "");
+        assert_eq!(html_to_text("plain   text\r\n here "), "plain text here");
+        assert_eq!(html_to_text("<HTML><Head><Title>Subject</Title>\n<STYLE>p { x: y }</STYLE></Head>\
+                                 <body>Hello<BR/>there<br>\n  world</body></HTML>"),
+                   "Hello\nthere\nworld");
+        assert_eq!(html_to_text("<p>One</p>\n<p>Two</p><div><div>Three</div></div><ul><li>a</li><li>b</li></ul>"),
+                   "One\n\nTwo\n\nThree\na\nb");
+        assert_eq!(html_to_text("a<!-- <p>hidden</p> -->b<script>if (a<b) x();</script>c"), "abc");
+        assert_eq!(html_to_text("<a href=\"x>y\">link</a> and <img src=x /> image"), "link and image");
+        assert_eq!(html_to_text("<a title='\"a>b\"' href=x>link</a>"), "link");
+        // Character references
+        assert_eq!(html_to_text("&lt;tag&gt; &amp;amp; &quot;&#65;&#x42;&#X43;&quot; &nbsp;&euro;"),
+                   "<tag> &amp; \"ABC\" \u{a0}\u{20ac}");
+        assert_eq!(html_to_text("AT&T &bogus; &#xZZ; &#1114112; &"), "AT&T &bogus; &#xZZ; &#1114112; &");
+        // Things that look like tags but aren't
+        assert_eq!(html_to_text("1 < 2 and 3 <= 4 <"), "1 < 2 and 3 <= 4 <");
+        assert_eq!(html_to_text("unterminated <b"), "unterminated");
+        assert_eq!(html_to_text("<script>never closed"), "");
+        assert_eq!(html_to_text("<br><p>a<br><br>b<div>c</div><p>d"), "a\n\nb\nc\n\nd");
+        assert_eq!(html_to_text("\u{e9}<br>\u{4e2d}&#20013;"), "\u{e9}\n\u{4e2d}\u{4e2d}");
+    }
+}
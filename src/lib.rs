@@ -1,7 +1,10 @@
 extern crate base64;
 extern crate encoding;
 extern crate quoted_printable;
+#[cfg(feature = "serde")]
+extern crate serde;
 
+use std::borrow::Cow;
 use std::error;
 use std::fmt;
 use std::ops::Deref;
@@ -9,9 +12,127 @@ use std::collections::BTreeMap;
 
 use encoding::Encoding;
 
+mod addrparse;
+mod authresults;
+mod charset;
 mod dateparse;
+mod flowed;
+mod html;
+mod msgidparse;
+mod partial;
+mod received;
+#[cfg(feature = "serde")]
+mod ser;
+mod uudecode;
+mod warnings;
 
+pub use addrparse::{addrparse, addrparse_header, GroupInfo, MailAddr, SingleInfo};
+pub use authresults::{parse_authentication_results, AuthProperty, AuthResult, AuthResults};
+pub use charset::{charset_encoding, charset_encoding_with_aliases, CHARSET_ALIASES};
 pub use dateparse::dateparse;
+pub use html::html_to_text;
+pub use msgidparse::msgidparse;
+pub use partial::reassemble_partial;
+pub use received::{parse_received, ReceivedHeader};
+pub use warnings::{parse_mail_with_warnings, Warning};
+
+/// The specific kind of problem behind a `MailParseError::Generic` error.
+/// This allows callers to match on the cause of a parse failure rather than
+/// on the human-readable description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// An empty string was provided where a header was expected.
+    EmptyInput,
+    /// A header started with a space or tab, so it is likely an overhanging line
+    /// from a previous header.
+    SpaceBeforeHeader,
+    /// A newline was found in the middle of a header key.
+    NewlineInKey,
+    /// The header had no colon separating the key from the value.
+    MissingColon,
+    /// The header block was followed by a carriage return that was not
+    /// part of a CRLF pair. This is no longer reported, as a bare CR is now
+    /// accepted as a line break, but the kind is kept so that existing code
+    /// matching on it still compiles.
+    LoneCarriageReturn,
+    /// Data with a uuencode transfer-encoding had no `begin` line.
+    MissingUuencodeBegin,
+    /// Data with a uuencode transfer-encoding contained a character that is
+    /// not valid in uuencoded data.
+    InvalidUuencodeCharacter,
+    /// A line of uuencoded data was shorter than its declared length.
+    TruncatedUuencodeLine,
+    /// A message-id was missing its closing angle bracket.
+    UnterminatedMessageId,
+    /// The input ended before the blank line that terminates the headers.
+    UnterminatedHeaders,
+    /// A header had an empty key. Only reported when parsing with
+    /// `ParseOptions::strict_keys`.
+    EmptyKey,
+    /// A header key contained a character that is not allowed by RFC 5322.
+    /// Only reported when parsing with `ParseOptions::strict_keys`.
+    InvalidKeyCharacter,
+    /// A part passed to `reassemble_partial` is not a valid message/partial
+    /// fragment of the message being reassembled.
+    InvalidPartialFragment,
+    /// A fragment of a message/partial message is missing or duplicated.
+    MissingPartialFragment,
+    /// An address in an address list was missing its closing angle bracket.
+    UnterminatedAddress,
+    /// An entry in an address list was not a valid mailbox or group.
+    InvalidAddress,
+    /// A message was nested (as multipart parts or encapsulated messages)
+    /// more deeply than `ParseOptions::max_depth` allows.
+    NestingTooDeep,
+    /// A date in a header could not be parsed.
+    InvalidDate,
+    /// A header was longer than `ParseOptions::max_header_size` allows.
+    HeaderTooLarge,
+    /// An RFC 2047 encoded-word in a header value contained bytes that are
+    /// invalid in its charset. Only reported by `MailHeader::get_value_strict`.
+    InvalidEncodedWord,
+    /// An Authentication-Results header was missing its server identifier,
+    /// or had a method without a result.
+    InvalidAuthResults,
+}
+
+impl ErrorKind {
+    /// Returns a human-readable description of the error kind.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            ErrorKind::EmptyInput => "Empty string provided",
+            ErrorKind::SpaceBeforeHeader => {
+                "Header cannot start with whitespace; it is likely an overhanging line from a \
+                 previous header"
+            }
+            ErrorKind::NewlineInKey => "Unexpected newline in header key",
+            ErrorKind::MissingColon => "Unable to determine end of the header key component",
+            ErrorKind::LoneCarriageReturn => "Headers were followed by an unexpected lone CR character!",
+            ErrorKind::MissingUuencodeBegin => "Unable to find uuencode begin line",
+            ErrorKind::InvalidUuencodeCharacter => "Invalid character in uuencoded data",
+            ErrorKind::TruncatedUuencodeLine => "Uuencoded line is shorter than its declared length",
+            ErrorKind::UnterminatedMessageId => "Message-id is missing its closing angle bracket",
+            ErrorKind::UnterminatedHeaders => "Headers were not terminated by a blank line",
+            ErrorKind::EmptyKey => "Header key is empty",
+            ErrorKind::InvalidKeyCharacter => "Invalid character in header key",
+            ErrorKind::InvalidPartialFragment => "Invalid message/partial fragment",
+            ErrorKind::MissingPartialFragment => "Missing or duplicate message/partial fragment",
+            ErrorKind::UnterminatedAddress => "Address is missing its closing angle bracket",
+            ErrorKind::InvalidAddress => "Invalid entry in address list",
+            ErrorKind::NestingTooDeep => "Message parts are nested too deeply",
+            ErrorKind::InvalidDate => "Unable to parse date",
+            ErrorKind::HeaderTooLarge => "Header exceeds the maximum allowed size",
+            ErrorKind::InvalidEncodedWord => "Encoded-word contains bytes that are invalid in its charset",
+            ErrorKind::InvalidAuthResults => "Invalid Authentication-Results header",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
 
 /// An error type that represents the different kinds of errors that may be
 /// encountered during message parsing.
@@ -26,9 +147,66 @@ pub enum MailParseError {
     /// An error occurred when converting the raw byte data to Rust UTF-8 string
     /// format using the charset specified in the message.
     EncodingError(std::borrow::Cow<'static, str>),
-    /// Some other error occurred while parsing the message; the description string
-    /// provides additional details.
-    Generic(&'static str),
+    /// The Content-Transfer-Encoding of the data is not one that this library
+    /// knows how to decode. The string holds the (lowercased) encoding name.
+    UnsupportedTransferEncoding(String),
+    /// The value of a header could not be decoded. The string holds the key
+    /// of the header, so that the culprit can be identified among many
+    /// headers, and the boxed error is the underlying problem. All the
+    /// `MailHeader::get_value*` functions (and so the `MailHeaderMap`
+    /// functions that use them) report their errors this way.
+    HeaderValueError(String, Box<MailParseError>),
+    /// Some other error occurred while parsing the message; the error kind
+    /// describes the problem, and the second value is the byte offset into
+    /// the input at which it was detected.
+    Generic(ErrorKind, usize),
+}
+
+impl MailParseError {
+    /// Convert the byte offset of a `Generic` error into a 1-based line and
+    /// column number within `input`, which should be the data that was passed
+    /// to the function that returned the error. Lines may be terminated by
+    /// CRLF, a bare LF, or a bare CR, and the column is counted in bytes.
+    /// Returns None for the other error variants, which don't have an offset,
+    /// or if the offset is beyond the end of `input`.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_headers;
+    ///     let input = b"Subject: Test\r\nBad header\r\n\r\n";
+    ///     let err = parse_headers(input).unwrap_err();
+    ///     // The error is for the line break found in the middle of the key
+    ///     assert_eq!(err.location(input), Some((2, 11)));
+    /// ```
+    pub fn location(&self, input: &[u8]) -> Option<(usize, usize)> {
+        let offset = match *self {
+            MailParseError::Generic(_, ix) if ix <= input.len() => ix,
+            _ => return None,
+        };
+        let mut line = 1;
+        let mut ix_line_start = 0;
+        for (ix, c) in input[..offset].iter().enumerate() {
+            let is_line_end = match *c {
+                b'\n' => true,
+                b'\r' => input.get(ix + 1) != Some(&b'\n'),
+                _ => false,
+            };
+            if is_line_end {
+                line += 1;
+                ix_line_start = ix + 1;
+            }
+        }
+        Some((line, offset - ix_line_start + 1))
+    }
+
+    /// Shift the byte offset of a `Generic` error by the given amount, so that
+    /// errors from parsing a slice are reported relative to the whole input.
+    fn offset_by(self, delta: usize) -> MailParseError {
+        match self {
+            MailParseError::Generic(kind, ix) => MailParseError::Generic(kind, ix + delta),
+            other => other,
+        }
+    }
 }
 
 impl fmt::Display for MailParseError {
@@ -39,25 +217,35 @@ impl fmt::Display for MailParseError {
             }
             MailParseError::Base64DecodeError(ref err) => write!(f, "Base64 decode error: {}", err),
             MailParseError::EncodingError(ref err) => write!(f, "Encoding error: {}", err),
-            MailParseError::Generic(ref description) => write!(f, "{}", description),
+            MailParseError::UnsupportedTransferEncoding(ref enc) => {
+                write!(f, "Unsupported transfer encoding: {}", enc)
+            }
+            MailParseError::HeaderValueError(ref key, ref err) => {
+                write!(f, "Error in value of header {}: {}", key, err)
+            }
+            MailParseError::Generic(ref kind, _) => write!(f, "{}", kind),
         }
     }
 }
 
 impl error::Error for MailParseError {
+    #[allow(deprecated)]
     fn description(&self) -> &str {
         match *self {
             MailParseError::QuotedPrintableDecodeError(ref err) => err.description(),
             MailParseError::Base64DecodeError(ref err) => err.description(),
             MailParseError::EncodingError(ref err) => err.deref(),
-            _ => "An error occurred while attempting to parse the input",
+            MailParseError::UnsupportedTransferEncoding(_) => "Unsupported transfer encoding",
+            MailParseError::HeaderValueError(_, ref err) => err.description(),
+            MailParseError::Generic(ref kind, _) => kind.description(),
         }
     }
 
-    fn cause(&self) -> Option<&error::Error> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
             MailParseError::QuotedPrintableDecodeError(ref err) => Some(err),
             MailParseError::Base64DecodeError(ref err) => Some(err),
+            MailParseError::HeaderValueError(_, ref err) => Some(err.as_ref()),
             _ => None,
         }
     }
@@ -86,39 +274,70 @@ impl From<std::borrow::Cow<'static, str>> for MailParseError {
 /// lifetime of this struct must be contained within the lifetime of the raw
 /// input. There are additional accessor functions on this struct to extract
 /// the data as Rust strings.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MailHeader<'a> {
     key: &'a [u8],
     value: &'a [u8],
 }
 
+/// A piece of the value of a header, as returned by `MailHeader::value_tokens`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderToken {
+    /// Text that was not encoded, after unfolding.
+    Literal(String),
+    /// The decoded text of an RFC 2047 encoded-word, along with the charset
+    /// label that the encoded-word specified.
+    EncodedWord { charset: String, text: String },
+}
+
+impl HeaderToken {
+    /// Get the (decoded) text of the token.
+    pub fn text(&self) -> &str {
+        match *self {
+            HeaderToken::Literal(ref text) => text,
+            HeaderToken::EncodedWord { ref text, .. } => text,
+        }
+    }
+}
+
+/// Check if the byte at the given offset may delimit an encoded-word. The
+/// start and end of the line (including an out-of-range offset) count as
+/// boundaries too. The offset is a byte offset, and since all the delimiting
+/// characters are ASCII, a byte in the middle of a multi-byte character is
+/// never a boundary.
 fn is_boundary(line: &str, ix: Option<usize>) -> bool {
-    ix.and_then(|v| line.chars().nth(v))
-        .map(|c| c.is_whitespace() || c == '"' || c == '(' || c == ')' || c == '<' || c == '>')
+    ix.and_then(|v| line.as_bytes().get(v))
+        .map(|&c| c.is_ascii_whitespace() || matches!(c, b'"' | b'(' | b')' | b'<' | b'>'))
         .unwrap_or(true)
 }
 
+/// Check if the byte at the given offset may delimit an encoded-word in a
+/// phrase, such as a display name in an address list. Besides the bytes
+/// accepted by `is_boundary`, this accepts the specials that separate the
+/// phrase from the rest of the list.
+fn is_phrase_boundary(line: &str, ix: Option<usize>) -> bool {
+    is_boundary(line, ix) ||
+        ix.and_then(|v| line.as_bytes().get(v)).is_some_and(|&c| matches!(c, b',' | b';' | b':'))
+}
+
+/// Check if the byte is allowed in a header key by RFC 5322.
+fn is_key_char(c: u8) -> bool {
+    (33..=126).contains(&c) && c != b':'
+}
+
 fn find_from(line: &str, ix_start: usize, key: &str) -> Option<usize> {
-    line[ix_start..].find(key).map(|v| ix_start + v)
+    line.get(ix_start..)?.find(key).map(|v| ix_start + v)
 }
 
 fn find_from_u8(line: &[u8], ix_start: usize, key: &[u8]) -> Option<usize> {
-    assert!(key.len() > 0);
-    assert!(ix_start < line.len());
-    let ix_end = line.len() - key.len();
+    if key.is_empty() || ix_start >= line.len() {
+        return None;
+    }
+    let ix_end = line.len().checked_sub(key.len())?;
     if ix_start <= ix_end {
-        for i in ix_start..ix_end {
-            if line[i] == key[0] {
-                let mut success = true;
-                for j in 1..key.len() {
-                    if line[i + j] != key[j] {
-                        success = false;
-                        break;
-                    }
-                }
-                if success {
-                    return Some(i);
-                }
+        for i in ix_start..=ix_end {
+            if line[i..].starts_with(key) {
+                return Some(i);
             }
         }
     }
@@ -132,7 +351,12 @@ fn test_find_from_u8() {
     assert_eq!(find_from_u8(b"hello world", 4, b"o"), Some(4));
     assert_eq!(find_from_u8(b"hello world", 5, b"o"), Some(7));
     assert_eq!(find_from_u8(b"hello world", 8, b"o"), None);
-    assert_eq!(find_from_u8(b"hello world", 10, b"d"), None);
+    assert_eq!(find_from_u8(b"hello world", 10, b"d"), Some(10));
+    assert_eq!(find_from_u8(b"hello world", 11, b"d"), None);
+    assert_eq!(find_from_u8(b"hello world", 50, b"d"), None);
+    assert_eq!(find_from_u8(b"hello world", 0, b""), None);
+    assert_eq!(find_from_u8(b"hi", 0, b"hello"), None);
+    assert_eq!(find_from_u8(b"", 0, b"x"), None);
 }
 
 impl<'a> MailHeader<'a> {
@@ -144,37 +368,112 @@ impl<'a> MailHeader<'a> {
             .map_err(|e| e.into())
     }
 
-    fn decode_word(&self, encoded: &str) -> Option<String> {
-        let ix_delim1 = encoded.find('?')?;
-        let ix_delim2 = find_from(encoded, ix_delim1 + 1, "?")?;
-
-        let charset = &encoded[0..ix_delim1];
-        let transfer_coding = &encoded[ix_delim1 + 1..ix_delim2];
-        let input = &encoded[ix_delim2 + 1..];
-
-        let decoded = match transfer_coding {
-            "B" | "b" => base64::decode(input.as_bytes()).ok()?,
-            "Q" | "q" => {
-                // The quoted_printable module does a trim_right on the input, so if
-                // that affects the output we should save and restore the trailing
-                // whitespace
-                let to_decode = input.replace("_", " ");
-                let trimmed = to_decode.trim_right();
-                let mut d =
-                    quoted_printable::decode(&trimmed, quoted_printable::ParseMode::Robust);
-                if d.is_ok() && to_decode.len() != trimmed.len() {
-                    d.as_mut().unwrap().extend_from_slice(
-                        to_decode[trimmed.len()..].as_bytes(),
-                    );
+    /// Get the raw bytes of the name of the header, exactly as they appear in
+    /// the input, i.e. everything before the colon (including any whitespace
+    /// before it).
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header(b"Subject : Hello").unwrap();
+    ///     assert_eq!(parsed.get_key_raw(), b"Subject ");
+    ///     assert_eq!(parsed.get_key().unwrap(), "Subject");
+    /// ```
+    pub fn get_key_raw(&self) -> &'a [u8] {
+        self.key
+    }
+
+    /// Get the raw bytes of the value of the header, borrowed from the input.
+    /// The value is still folded, with the original line breaks and the
+    /// whitespace at the start of continuation lines intact, and no decoding
+    /// is done. The value starts after the spaces that follow the colon, and
+    /// ends before the line terminator of its last line. Together with
+    /// `get_key_raw`, this gives access to the original bytes of the header,
+    /// e.g. for DKIM canonicalization.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header(b"Subject: Hello,\r\n\tworld\r\n").unwrap();
+    ///     assert_eq!(parsed.get_value_bytes(), b"Hello,\r\n\tworld");
+    ///     assert_eq!(parsed.get_value().unwrap(), "Hello, world");
+    /// ```
+    pub fn get_value_bytes(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Check if the name of the header is `name`, ignoring ASCII case. This
+    /// gives the same result as comparing against `get_key()`, but without
+    /// allocating, so it is cheaper when looking for a particular header.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header(b"Content-Type: text/plain").unwrap();
+    ///     assert!(parsed.key_is("content-type"));
+    ///     assert!(!parsed.key_is("Content"));
+    /// ```
+    pub fn key_is(&self, name: &str) -> bool {
+        // The key is ISO-8859-1, so each byte corresponds to exactly one char.
+        let is_space = |c: &&u8| (**c as char).is_whitespace();
+        let start = self.key.iter().take_while(is_space).count();
+        let end = self.key.len() - self.key[start..].iter().rev().take_while(is_space).count();
+        let key = &self.key[start..end];
+        key.len() == name.chars().count()
+            && key
+                .iter()
+                .zip(name.chars())
+                .all(|(b, c)| (*b as char).eq_ignore_ascii_case(&c))
+    }
+
+    /// Iterate over the physical lines making up the raw value of the header,
+    /// in the order in which they appear. The line terminators (CRLF, LF or a
+    /// bare CR) are removed, but the whitespace at the start of continuation
+    /// lines is kept, so this can be used to see or reproduce how the header
+    /// was folded. An unfolded header yields a single line.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header(b"Subject: Hello,\r\n  world\r\n\tagain").unwrap();
+    ///     let lines: Vec<&[u8]> = parsed.raw_value_lines().collect();
+    ///     assert_eq!(lines, vec![&b"Hello,"[..], &b"  world"[..], &b"\tagain"[..]]);
+    /// ```
+    pub fn raw_value_lines(&self) -> impl Iterator<Item = &'a [u8]> {
+        let mut rest = Some(self.value);
+        std::iter::from_fn(move || {
+            let line = rest?;
+            match line.iter().position(|c| *c == b'\n' || *c == b'\r') {
+                Some(ix) => {
+                    let is_crlf = line[ix] == b'\r' && line.get(ix + 1) == Some(&b'\n');
+                    rest = Some(&line[ix + if is_crlf { 2 } else { 1 }..]);
+                    Some(&line[..ix])
+                }
+                None => {
+                    rest = None;
+                    Some(line)
                 }
-                d.ok()?
             }
-            _ => return None,
-        };
-        let charset_conv = encoding::label::encoding_from_whatwg_label(charset)?;
-        charset_conv
-            .decode(&decoded, encoding::DecoderTrap::Replace)
-            .ok()
+        })
+    }
+
+    /// Check if the raw name of the header is valid according to RFC 5322,
+    /// i.e. it is non-empty and consists only of printable US-ASCII characters
+    /// other than the colon (bytes 33 to 126, excluding 58). Note that this
+    /// means a key with whitespace before the colon, which the parser accepts
+    /// by default, is not valid. Parsing with `ParseOptions::strict_keys`
+    /// rejects headers with invalid keys up front.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header(b"X-Spam-Score: 5").unwrap();
+    ///     assert!(parsed.is_valid_key());
+    ///     let (parsed, _) = parse_header(b"X-Spam\x07Score: 5").unwrap();
+    ///     assert!(!parsed.is_valid_key());
+    /// ```
+    pub fn is_valid_key(&self) -> bool {
+        !self.key.is_empty() && self.key.iter().all(|c| is_key_char(*c))
     }
 
     /// Get the value of the header. Any sequences of newlines characters followed
@@ -191,13 +490,244 @@ impl<'a> MailHeader<'a> {
     ///     assert_eq!(parsed.get_value().unwrap(), "\u{a1}Hola, se\u{f1}or!");
     /// ```
     pub fn get_value(&self) -> Result<String, MailParseError> {
+        self.get_value_with_trap(encoding::DecoderTrap::Replace)
+    }
+
+    /// Get the value of the header, as with `get_value`, but using the given
+    /// trap to handle bytes that cannot be decoded in the relevant charset.
+    /// The trap applies to the decoding of the raw header bytes as well as to
+    /// the charset conversion of any encoded-words. With a strict trap, an
+    /// encoded-word whose contents are invalid in its charset is left as-is
+    /// in the output, just like an encoded-word that cannot be decoded at all.
+    /// `get_value` uses `DecoderTrap::Replace`.
+    ///
+    /// # Examples
+    /// ```
+    ///     extern crate encoding;
+    ///     extern crate mailparse;
+    ///     use encoding::DecoderTrap;
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header(b"Invalid: =?utf-8?Q?=E2=AC?=").unwrap();
+    ///     assert_eq!(parsed.get_value_with_trap(DecoderTrap::Replace).unwrap(), "\u{fffd}");
+    ///     assert_eq!(parsed.get_value_with_trap(DecoderTrap::Strict).unwrap(), "=?utf-8?Q?=E2=AC?=");
+    /// ```
+    pub fn get_value_with_trap(&self, trap: encoding::DecoderTrap) -> Result<String, MailParseError> {
+        self.decode_value(&ValueDecodeOptions { trap, ..Default::default() })
+    }
+
+    /// Get the value of the header, as with `get_value`, but using the given
+    /// mode to decode Q encoded-words. `get_value` uses `ParseMode::Robust`,
+    /// which tolerates malformed words as they are common in incoming mail.
+    /// Passing `ParseMode::Strict` instead causes a `QuotedPrintableDecodeError`
+    /// (wrapped in a `HeaderValueError`) to be returned for a Q encoded-word
+    /// with an invalid `=` escape, lowercase hex digits, or bytes that are not
+    /// printable ASCII, which is useful to validate outgoing mail.
+    ///
+    /// # Examples
+    /// ```
+    ///     extern crate mailparse;
+    ///     extern crate quoted_printable;
+    ///     use mailparse::parse_header;
+    ///     use quoted_printable::ParseMode;
+    ///     let (parsed, _) = parse_header(b"Subject: =?utf-8?Q?a=3Db?=").unwrap();
+    ///     assert_eq!(parsed.get_value_with_qp_mode(ParseMode::Strict).unwrap(), "a=b");
+    ///     let (parsed, _) = parse_header(b"Subject: =?utf-8?Q?a=3db=?=").unwrap();
+    ///     assert_eq!(parsed.get_value_with_qp_mode(ParseMode::Robust).unwrap(), "a=b=");
+    ///     assert!(parsed.get_value_with_qp_mode(ParseMode::Strict).is_err());
+    /// ```
+    pub fn get_value_with_qp_mode(
+        &self,
+        qp_mode: quoted_printable::ParseMode,
+    ) -> Result<String, MailParseError> {
+        self.decode_value(&ValueDecodeOptions { qp_mode, ..Default::default() })
+    }
+
+    /// Get the value of the header, as with `get_value`, but returning an
+    /// error for an encoded-word whose contents are not valid in its charset,
+    /// rather than replacing the invalid bytes with U+FFFD. The error is an
+    /// `InvalidEncodedWord` error at the offset of the encoded-word in the
+    /// raw value (see `get_value_bytes`), wrapped in a `HeaderValueError`.
+    /// This is useful to flag corrupt encoded-words when validating mail.
+    /// Encoded-words that can't be decoded at all, e.g. because of an unknown
+    /// charset, are still left as-is.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_header, ErrorKind, MailParseError};
+    ///     let (parsed, _) = parse_header(b"Subject: =?utf-8?Q?caf=C3=A9?=").unwrap();
+    ///     assert_eq!(parsed.get_value_strict().unwrap(), "caf\u{e9}");
+    ///     let (parsed, _) = parse_header(b"Subject: ok =?utf-8?Q?caf=E9?=").unwrap();
+    ///     assert_eq!(parsed.get_value().unwrap(), "ok caf\u{fffd}");
+    ///     match parsed.get_value_strict().unwrap_err() {
+    ///         MailParseError::HeaderValueError(key, err) => {
+    ///             assert_eq!(key, "Subject");
+    ///             match *err {
+    ///                 MailParseError::Generic(ErrorKind::InvalidEncodedWord, 3) => (),
+    ///                 _ => panic!("Unexpected error {:?}", err),
+    ///             }
+    ///         }
+    ///         err => panic!("Unexpected error {:?}", err),
+    ///     }
+    /// ```
+    pub fn get_value_strict(&self) -> Result<String, MailParseError> {
+        self.decode_value(&ValueDecodeOptions { strict_words: true, ..Default::default() })
+    }
+
+    /// Get the value of the header, as with `get_value`, but decoding the raw
+    /// bytes of the value in the given charset instead of ISO-8859-1. RFC 5322
+    /// only allows ASCII in header values, with other characters encoded in
+    /// RFC 2047 encoded-words, but some non-conforming senders put e.g. raw
+    /// UTF-8 in the Subject. If the charset is not recognized, ISO-8859-1 is
+    /// used. Any encoded-words in the value are decoded as usual.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header("Subject: Caf\u{e9} =?utf-8?Q?cr=C3=A8me?=".as_bytes()).unwrap();
+    ///     assert_eq!(parsed.get_value().unwrap(), "Caf\u{c3}\u{a9} cr\u{e8}me");
+    ///     assert_eq!(parsed.get_value_charset("utf-8").unwrap(), "Caf\u{e9} cr\u{e8}me");
+    /// ```
+    pub fn get_value_charset(&self, charset: &str) -> Result<String, MailParseError> {
+        let charset = charset_encoding(charset).unwrap_or(encoding::all::ISO_8859_1);
+        self.decode_value(&ValueDecodeOptions { charset, ..Default::default() })
+    }
+
+    /// Get the value of the header, as with `get_value`, but tolerating
+    /// encoded-words that have been split across folded lines. RFC 2047 does
+    /// not allow folding whitespace inside an encoded-word, so `get_value`
+    /// leaves such words undecoded. However some mailers do this anyway,
+    /// intending for the pieces to be concatenated, and this function does
+    /// that before decoding.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header(b"Euro: =?utf-8?Q?=E2=82\n =AC?=").unwrap();
+    ///     assert_eq!(parsed.get_value().unwrap(), "=?utf-8?Q?=E2=82 =AC?=");
+    ///     assert_eq!(parsed.get_value_tolerant().unwrap(), "\u{20ac}");
+    /// ```
+    pub fn get_value_tolerant(&self) -> Result<String, MailParseError> {
+        self.decode_value(&ValueDecodeOptions { join_split_words: true, ..Default::default() })
+    }
+
+    /// Get the value of the header, as with `get_value`, but unfolding it the
+    /// canonical way described in RFC 5322, by removing just the line breaks.
+    /// The whitespace at the start of continuation lines, and any runs of
+    /// whitespace within lines, are kept as they are. This is useful when the
+    /// exact value matters, e.g. for matching a Subject against a filter.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header(b"Subject: [list]  Two\n   spaces ").unwrap();
+    ///     assert_eq!(parsed.get_value().unwrap(), "[list]  Two spaces ");
+    ///     assert_eq!(parsed.get_value_preserve_ws().unwrap(), "[list]  Two   spaces ");
+    /// ```
+    pub fn get_value_preserve_ws(&self) -> Result<String, MailParseError> {
+        self.decode_value(&ValueDecodeOptions { preserve_ws: true, ..Default::default() })
+    }
+
+    /// Get the value of the header, as with `get_value`, but decoding it as a
+    /// structured value made up of phrases, such as an address list. In
+    /// unstructured text like a Subject, RFC 2047 requires encoded-words to be
+    /// separated from the surrounding text by whitespace (quotes, parentheses
+    /// and angle brackets are accepted as well). In a phrase an encoded-word
+    /// may also be directly followed or preceded by a special such as `,`,
+    /// `;` or `:`, so such words are decoded here too. Note that `addrparse`
+    /// and `MailHeaderMap::get_addresses` split the address list up before
+    /// decoding the display names, which is usually what you want.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_header;
+    ///     let (parsed, _) = parse_header(b"To: =?utf-8?Q?J=C3=B6rg?=<j@x>,=?utf-8?Q?Bob?=, c@x").unwrap();
+    ///     assert_eq!(parsed.get_value().unwrap(), "J\u{f6}rg<j@x>,=?utf-8?Q?Bob?=, c@x");
+    ///     assert_eq!(parsed.get_value_phrase().unwrap(), "J\u{f6}rg<j@x>,Bob, c@x");
+    /// ```
+    pub fn get_value_phrase(&self) -> Result<String, MailParseError> {
+        self.decode_value(&ValueDecodeOptions { phrase: true, ..Default::default() })
+    }
+
+    /// Get the value of the header, as with `get_value`, but split into the
+    /// pieces that were literal text and the pieces that were RFC 2047
+    /// encoded-words. The text of all the tokens together is the same as the
+    /// result of `get_value`. Adjacent literal pieces are merged into a single
+    /// token, whereas every encoded-word is a token of its own, along with the
+    /// charset label it specified (as written in the header, without any
+    /// language tag). Malformed encoded-words that could not be decoded are
+    /// part of the literal text.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_header, HeaderToken};
+    ///     let (parsed, _) = parse_header(b"Subject: Re: =?ISO-8859-1?Q?caf=E9?= =?utf-8?B?w6k=?= ok").unwrap();
+    ///     assert_eq!(parsed.value_tokens().unwrap(), vec![
+    ///         HeaderToken::Literal("Re: ".to_string()),
+    ///         HeaderToken::EncodedWord { charset: "ISO-8859-1".to_string(), text: "caf\u{e9}".to_string() },
+    ///         HeaderToken::Literal(" ".to_string()),
+    ///         HeaderToken::EncodedWord { charset: "utf-8".to_string(), text: "\u{e9}".to_string() },
+    ///         HeaderToken::Literal(" ok".to_string()),
+    ///     ]);
+    /// ```
+    pub fn value_tokens(&self) -> Result<Vec<HeaderToken>, MailParseError> {
+        self.decode_value_tokens(&ValueDecodeOptions::default())
+            .map_err(|err| self.value_error(err))
+    }
+
+    fn decode_value(&self, options: &ValueDecodeOptions) -> Result<String, MailParseError> {
+        let tokens = self.decode_value_tokens(options).map_err(|err| self.value_error(err))?;
         let mut result = String::new();
-        let chars = encoding::all::ISO_8859_1.decode(self.value, encoding::DecoderTrap::Strict)?;
-        let mut lines = chars.lines();
+        for token in &tokens {
+            result.push_str(token.text());
+        }
+        Ok(result)
+    }
+
+    /// Wrap an error from decoding the value so that it identifies the header.
+    fn value_error(&self, err: MailParseError) -> MailParseError {
+        let key = encoding::all::ISO_8859_1
+            .decode(self.key, encoding::DecoderTrap::Replace)
+            .unwrap_or_default();
+        MailParseError::HeaderValueError(key.trim().to_string(), Box::new(err))
+    }
+
+    fn decode_value_tokens(
+        &self,
+        options: &ValueDecodeOptions,
+    ) -> Result<Vec<HeaderToken>, MailParseError> {
+        let trap = options.trap;
+        let word_trap = if options.strict_words { encoding::DecoderTrap::Strict } else { trap };
+        let is_boundary = if options.phrase { is_phrase_boundary } else { is_boundary };
+        let mut tokens = Vec::new();
+        let chars = options.charset.decode(self.value, trap)?;
+        // The folded lines may be separated by CRLF, LF, or bare CR, so split
+        // on both characters and drop the empty piece between a CR and LF.
+        let mut lines: Vec<Cow<str>> = Vec::new();
+        if options.preserve_ws {
+            // Canonical unfolding only removes the line breaks, so the value
+            // becomes a single line with all of the whitespace intact.
+            lines.push(Cow::Owned(chars.chars().filter(|c| *c != '\r' && *c != '\n').collect()));
+        } else {
+            for line in chars
+                .split(['\r', '\n'])
+                .filter(|line| !line.is_empty())
+                .map(str::trim_start)
+            {
+                if options.join_split_words {
+                    if let Some(last) = lines.last_mut() {
+                        if has_unterminated_word(last) {
+                            last.to_mut().push_str(line);
+                            continue;
+                        }
+                    }
+                }
+                lines.push(Cow::Borrowed(line));
+            }
+        }
         let mut add_space = false;
-        while let Some(line) = lines.next().map(str::trim_left) {
+        for line in &lines {
             if add_space {
-                result.push(' ');
+                push_literal(&mut tokens, " ");
             }
             add_space = true;
 
@@ -207,11 +737,11 @@ impl<'a> MailHeader<'a> {
                     Some(v) => {
                         let ix_begin = v + 2;
                         if !is_boundary(line, ix_begin.checked_sub(3)) {
-                            result.push_str(&line[ix_search..ix_begin]);
+                            push_literal(&mut tokens, &line[ix_search..ix_begin]);
                             ix_search = ix_begin;
                             continue;
                         }
-                        result.push_str(&line[ix_search..ix_begin - 2]);
+                        push_literal(&mut tokens, &line[ix_search..ix_begin - 2]);
                         let mut ix_end_search = ix_begin;
                         loop {
                             match find_from(line, ix_end_search, "?=") {
@@ -220,32 +750,336 @@ impl<'a> MailHeader<'a> {
                                         ix_end_search = ix_end + 2;
                                         continue;
                                     }
-                                    match self.decode_word(&line[ix_begin..ix_end]) {
-                                        Some(v) => {
-                                            result.push_str(&v);
+                                    let word = &line[ix_begin..ix_end];
+                                    let decoded = decode_word(word, word_trap, options.qp_mode.clone())?;
+                                    if decoded.is_none() && options.strict_words &&
+                                        decode_word(word, trap, options.qp_mode.clone())?.is_some()
+                                    {
+                                        let raw_word = line[ix_begin - 2..ix_end + 2].as_bytes();
+                                        let offset = self
+                                            .value
+                                            .windows(raw_word.len())
+                                            .position(|w| w == raw_word)
+                                            .unwrap_or(0);
+                                        return Err(MailParseError::Generic(
+                                            ErrorKind::InvalidEncodedWord,
+                                            offset,
+                                        ));
+                                    }
+                                    match decoded {
+                                        Some(text) => {
+                                            let charset = word.split(['?', '*']).next().unwrap_or("");
+                                            tokens.push(HeaderToken::EncodedWord {
+                                                charset: charset.to_string(),
+                                                text,
+                                            });
                                             add_space = false;
                                         }
-                                        None => result.push_str(&line[ix_begin - 2..ix_end + 2]),
+                                        None => push_literal(&mut tokens, &line[ix_begin - 2..ix_end + 2]),
                                     };
-                                    ix_search = ix_end;
+                                    ix_search = ix_end + 2;
                                 }
                                 None => {
-                                    result.push_str(&"=?");
+                                    push_literal(&mut tokens, "=?");
+                                    ix_search = ix_begin;
                                 }
                             };
                             break;
                         }
-                        ix_search += 2;
                         continue;
                     }
                     None => {
-                        result.push_str(&line[ix_search..]);
+                        push_literal(&mut tokens, &line[ix_search..]);
                         break;
                     }
                 };
             }
         }
-        Ok(result)
+        Ok(tokens)
+    }
+}
+
+/// Add literal text to the tokens of a header value, merging it with the
+/// previous token if that is literal text as well.
+fn push_literal(tokens: &mut Vec<HeaderToken>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(HeaderToken::Literal(last)) = tokens.last_mut() {
+        last.push_str(text);
+        return;
+    }
+    tokens.push(HeaderToken::Literal(text.to_string()));
+}
+
+/// Decode the contents of an RFC 2047 encoded-word, i.e. the `charset?enc?text`
+/// part between the `=?` and `?=` delimiters. Returns None if the word is
+/// malformed or uses an unknown charset or encoding. With a strict `qp_mode`,
+/// a malformed Q encoded-word results in an error instead.
+fn decode_word(
+    encoded: &str,
+    trap: encoding::DecoderTrap,
+    qp_mode: quoted_printable::ParseMode,
+) -> Result<Option<String>, MailParseError> {
+    let (ix_delim1, ix_delim2) = match encoded.find('?') {
+        Some(ix_delim1) => match find_from(encoded, ix_delim1 + 1, "?") {
+            Some(ix_delim2) => (ix_delim1, ix_delim2),
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+
+    // RFC 2231 allows a language tag after the charset, as in `utf-8*en`
+    let charset = encoded[0..ix_delim1].split('*').next().unwrap_or("");
+    let transfer_coding = &encoded[ix_delim1 + 1..ix_delim2];
+    let input = &encoded[ix_delim2 + 1..];
+
+    let decoded = match transfer_coding {
+        "B" | "b" => {
+            let standard = input.bytes().map(to_standard_base64).collect::<Vec<u8>>();
+            match base64::decode(&standard) {
+                Ok(decoded) => decoded,
+                Err(_) => return Ok(None),
+            }
+        }
+        "Q" | "q" => decode_q(input.as_bytes(), qp_mode)?,
+        _ => return Ok(None),
+    };
+    Ok(charset_encoding(charset).and_then(|charset_conv| charset_conv.decode(&decoded, trap).ok()))
+}
+
+/// Encode text as a sequence of RFC 2047 encoded-words using the B encoding,
+/// for use in a header value such as a Subject. The text is converted to the
+/// given charset, falling back to UTF-8 if the charset is not recognized, and
+/// characters that can't be represented in the charset are replaced by `?`.
+/// Each encoded-word is at most 75 characters long, as RFC 2047 requires, and
+/// the text is split between words at character boundaries only. The words
+/// are separated by a CRLF and a space, i.e. they are folded onto separate
+/// lines; decoding the resulting value (e.g. with `MailHeader::get_value`)
+/// gives back the original text. Empty text gives an empty string.
+///
+/// # Examples
+/// ```
+///     use mailparse::{encode_header_word, parse_header};
+///     assert_eq!(encode_header_word("Caf\u{e9}", "utf-8"), "=?utf-8?B?Q2Fmw6k=?=");
+///     assert_eq!(encode_header_word("Caf\u{e9}", "iso-8859-1"), "=?iso-8859-1?B?Q2Fm6Q==?=");
+///     let subject = "Gr\u{fc}\u{df}e aus K\u{f6}ln, und noch viel mehr Text, der nicht in ein Wort passt";
+///     let value = encode_header_word(subject, "utf-8");
+///     assert_eq!(value.split("\r\n ").count(), 2);
+///     let raw = format!("Subject: {}", value);
+///     let (header, _) = parse_header(raw.as_bytes()).unwrap();
+///     assert_eq!(header.get_value().unwrap(), subject);
+/// ```
+pub fn encode_header_word(text: &str, charset: &str) -> String {
+    let (charset, charset_conv) = match charset_encoding(charset) {
+        Some(charset_conv) => (charset.trim(), charset_conv),
+        None => ("utf-8", encoding::all::UTF_8 as encoding::EncodingRef),
+    };
+    let encode = |chunk: &str| {
+        charset_conv
+            .encode(chunk, encoding::EncoderTrap::Replace)
+            .unwrap_or_default()
+    };
+    // The `=?charset?B?` and `?=` delimiters take up charset.len() + 7
+    // characters, and every 3 bytes of data take up 4 characters of base64.
+    let max_bytes = 75usize.saturating_sub(charset.len() + 7) / 4 * 3;
+
+    let mut words: Vec<String> = Vec::new();
+    let mut chunk = String::new();
+    let mut bytes = Vec::new();
+    for c in text.chars() {
+        let start = chunk.len();
+        chunk.push(c);
+        let encoded = encode(&chunk);
+        if encoded.len() > max_bytes && start > 0 {
+            words.push(format!("=?{}?B?{}?=", charset, base64::encode(&bytes)));
+            chunk.drain(..start);
+            bytes = encode(&chunk);
+        } else {
+            bytes = encoded;
+        }
+    }
+    if !chunk.is_empty() {
+        words.push(format!("=?{}?B?{}?=", charset, base64::encode(&bytes)));
+    }
+    words.join("\r\n ")
+}
+
+/// Map the characters of the URL-safe base64 alphabet that differ from the
+/// standard alphabet to their standard equivalents. Some broken senders use
+/// the URL-safe alphabet, and since `-` and `_` never appear in standard
+/// base64 it is safe to always accept both.
+fn to_standard_base64(c: u8) -> u8 {
+    match c {
+        b'-' => b'+',
+        b'_' => b'/',
+        c => c,
+    }
+}
+
+/// Compute the length of the decoded form of a base64 body without decoding
+/// it, skipping whitespace as `ParsedMail::get_body_raw` does. Returns None
+/// if the body is not well-formed base64, in which case decoding it is the
+/// only way to find out whether (and how) the base64 decoder accepts it.
+fn base64_decoded_len(body: &[u8]) -> Option<usize> {
+    let mut data_len = 0;
+    let mut padding_len = 0;
+    for c in body.iter().filter(|c| !c.is_ascii_whitespace()) {
+        match to_standard_base64(*c) {
+            b'=' => padding_len += 1,
+            c if padding_len == 0 && (c.is_ascii_alphanumeric() || c == b'+' || c == b'/') => {
+                data_len += 1
+            }
+            _ => return None,
+        }
+    }
+    // Padding may only fill up the last group of 4 characters, which must
+    // have at least 2 data characters in it. Without padding, a single data
+    // character in the last group doesn't encode a whole byte.
+    let valid = match padding_len {
+        0 => data_len % 4 != 1,
+        _ => data_len % 4 >= 2 && data_len % 4 + padding_len <= 4,
+    };
+    if valid {
+        Some(data_len * 3 / 4)
+    } else {
+        None
+    }
+}
+
+/// Compute the length of the decoded form of a quoted-printable body without
+/// decoding it. This follows the robust decoding done by the
+/// `quoted_printable` crate, so characters other than printable ASCII are
+/// dropped, trailing whitespace is removed from every line, hard line breaks
+/// become CRLF, and malformed `=` escapes are kept as-is.
+fn quoted_printable_decoded_len(body: &[u8]) -> usize {
+    let is_qp_char = |c: &u8| matches!(*c, b'\t' | b'\r' | b' '..=b'~');
+    let mut len = 0;
+    let mut lines = body.split(|c| *c == b'\n').peekable();
+    let mut add_line_break = false;
+    while let Some(line) = lines.next() {
+        if lines.peek().is_none() && !line.iter().any(is_qp_char) {
+            // The line break at the end of the body doesn't start a new line,
+            // but it is kept as a CRLF even after a soft line break
+            if line.len() < body.len() {
+                len += 2;
+            }
+            break;
+        }
+        if add_line_break {
+            len += 2;
+        }
+        let line_end = line
+            .iter()
+            .rposition(|c| is_qp_char(c) && !matches!(*c, b' ' | b'\t' | b'\r'))
+            .map_or(0, |ix| ix + 1);
+        let mut bytes = line[..line_end].iter().filter(|c| is_qp_char(c));
+        add_line_break = true;
+        while let Some(c) = bytes.next() {
+            if *c != b'=' {
+                len += 1;
+                continue;
+            }
+            match (bytes.next(), bytes.next()) {
+                // A soft line break
+                (None, _) => add_line_break = false,
+                (Some(_), None) => len += 2,
+                (Some(upper), Some(lower)) => {
+                    len += if upper.is_ascii_hexdigit() && lower.is_ascii_hexdigit() { 1 } else { 3 };
+                }
+            }
+        }
+    }
+    len
+}
+
+/// Decode the text of a Q encoded-word. This is like quoted-printable, except
+/// that an underscore stands for a space and there are no soft line breaks,
+/// so an `=` that isn't followed by two hex digits (e.g. a trailing one) is
+/// kept as a literal `=` rather than being dropped along with what follows.
+/// In strict mode such an `=`, lowercase hex digits, and bytes other than
+/// printable ASCII result in an error instead, as with quoted-printable.
+fn decode_q(
+    input: &[u8],
+    mode: quoted_printable::ParseMode,
+) -> Result<Vec<u8>, quoted_printable::QuotedPrintableError> {
+    use quoted_printable::QuotedPrintableError;
+    let strict = mode == quoted_printable::ParseMode::Strict;
+    let hex_value = |c: u8| {
+        if strict && c.is_ascii_lowercase() {
+            return None;
+        }
+        (c as char).to_digit(16).map(|v| v as u8)
+    };
+    let mut result = Vec::with_capacity(input.len());
+    let mut ix = 0;
+    while ix < input.len() {
+        match input[ix] {
+            b'_' => result.push(b' '),
+            b'=' => {
+                let hex = input.get(ix + 1).and_then(|c| hex_value(*c)).and_then(|hi| {
+                    input.get(ix + 2).and_then(|c| hex_value(*c)).map(|lo| (hi << 4) | lo)
+                });
+                if let Some(byte) = hex {
+                    result.push(byte);
+                    ix += 2;
+                } else if strict && ix + 2 >= input.len() {
+                    return Err(QuotedPrintableError::IncompleteHexOctet);
+                } else if strict {
+                    return Err(QuotedPrintableError::InvalidHexOctet);
+                } else {
+                    result.push(b'=');
+                }
+            }
+            c if strict && !(33..=126).contains(&c) => {
+                return Err(QuotedPrintableError::InvalidByte)
+            }
+            c => result.push(c),
+        }
+        ix += 1;
+    }
+    Ok(result)
+}
+
+/// Settings that control the details of how `MailHeader::decode_value` turns
+/// the raw header value into a string. The public `get_value*` functions
+/// document what the individual settings do.
+struct ValueDecodeOptions {
+    charset: encoding::EncodingRef,
+    trap: encoding::DecoderTrap,
+    qp_mode: quoted_printable::ParseMode,
+    join_split_words: bool,
+    preserve_ws: bool,
+    phrase: bool,
+    strict_words: bool,
+}
+
+impl Default for ValueDecodeOptions {
+    fn default() -> Self {
+        ValueDecodeOptions {
+            charset: encoding::all::ISO_8859_1,
+            trap: encoding::DecoderTrap::Replace,
+            qp_mode: quoted_printable::ParseMode::Robust,
+            join_split_words: false,
+            preserve_ws: false,
+            phrase: false,
+            strict_words: false,
+        }
+    }
+}
+
+/// Check if the line ends in the middle of an encoded-word, i.e. the last
+/// encoded-word in the line has the charset and encoding but no terminating
+/// `?=`.
+fn has_unterminated_word(line: &str) -> bool {
+    let ix_begin = match line.rfind("=?") {
+        Some(v) if is_boundary(line, v.checked_sub(1)) => v + 2,
+        _ => return false,
+    };
+    let mut parts = line[ix_begin..].splitn(3, '?');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(_), Some(text)) => !text.contains("?="),
+        _ => false,
     }
 }
 
@@ -255,6 +1089,7 @@ enum HeaderParseState {
     Key,
     PreValue,
     Value,
+    ValueCarriageReturn,
     ValueNewline,
 }
 
@@ -265,24 +1100,68 @@ enum HeaderParseState {
 /// expected to start. If you just want to parse a single header, you can ignore
 /// the second component of the tuple, which is the index of the next header.
 /// Error values are returned if the data could not be successfully interpreted
-/// as a MIME key-value pair.
+/// as a MIME key-value pair. Lines may be terminated by CRLF, a bare LF, or a
+/// bare CR.
+///
+/// The key always ends at the first colon, since RFC 5322 doesn't allow
+/// colons in header keys. Keys have no quoting, so quotes or other characters
+/// before the first colon are part of the key (use `ParseOptions::strict_keys`
+/// to reject them), and any further colons are part of the value.
+///
+/// The returned index always includes the line terminator of the header's
+/// last line, so `&raw_data[ix..]` starts with the first byte of the next
+/// line (which may be the blank line that ends the headers). If the header
+/// is the last thing in the data, with or without a line terminator, the
+/// index is `raw_data.len()`.
 ///
 /// # Examples
 /// ```
 ///     use mailparse::parse_header;
-///     let (parsed, _) = parse_header(concat!(
-///             "Subject: Hello, sir,\n",
-///             "   I am multiline\n",
-///             "Next:Header").as_bytes())
-///         .unwrap();
+///     let raw = concat!(
+///             "Subject: Hello, sir,\r\n",
+///             "   I am multiline\r\n",
+///             "Next:Header").as_bytes();
+///     let (parsed, ix) = parse_header(raw).unwrap();
 ///     assert_eq!(parsed.get_key().unwrap(), "Subject");
 ///     assert_eq!(parsed.get_value().unwrap(), "Hello, sir, I am multiline");
+///     assert_eq!(&raw[ix..], b"Next:Header");
+///     let (parsed, ix) = parse_header(&raw[ix..]).unwrap();
+///     assert_eq!(parsed.get_value().unwrap(), "Header");
+///     assert_eq!(ix, 11);
+/// ```
+pub fn parse_header(raw_data: &[u8]) -> Result<(MailHeader<'_>, usize), MailParseError> {
+    parse_header_with_options(raw_data, &ParseOptions::default())
+}
+
+/// Parses a single header, as with `parse_header`, but using the given options
+/// to control how strict the parsing is. Of the options, only `strict_keys`,
+/// `allow_flag_headers` and `max_header_size` are relevant here.
+///
+/// # Examples
 /// ```
-pub fn parse_header(raw_data: &[u8]) -> Result<(MailHeader, usize), MailParseError> {
+///     use mailparse::{parse_header_with_options, ErrorKind, MailParseError, ParseOptions};
+///     let options = ParseOptions { strict_keys: true, ..Default::default() };
+///     let (parsed, _) = parse_header_with_options(b"Subject: Test", &options).unwrap();
+///     assert_eq!(parsed.get_key().unwrap(), "Subject");
+///     match parse_header_with_options(b"Bad Key: Test", &options) {
+///         Err(MailParseError::Generic(ErrorKind::InvalidKeyCharacter, 3)) => (),
+///         _ => panic!("Space in key was not detected"),
+///     }
+///     let options = ParseOptions { max_header_size: Some(16), ..Default::default() };
+///     assert!(parse_header_with_options(b"Subject: 16bytes\n", &options).is_ok());
+///     match parse_header_with_options(b"Subject: Too long\n", &options) {
+///         Err(MailParseError::Generic(ErrorKind::HeaderTooLarge, 0)) => (),
+///         _ => panic!("Oversized header was not detected"),
+///     }
+/// ```
+pub fn parse_header_with_options<'a>(
+    raw_data: &'a [u8],
+    options: &ParseOptions,
+) -> Result<(MailHeader<'a>, usize), MailParseError> {
     let mut it = raw_data.iter();
     let mut ix = 0;
     let mut c = match it.next() {
-        None => return Err(MailParseError::Generic("Empty string provided")),
+        None => return Err(MailParseError::Generic(ErrorKind::EmptyInput, 0)),
         Some(v) => *v,
     };
 
@@ -294,12 +1173,8 @@ pub fn parse_header(raw_data: &[u8]) -> Result<(MailHeader, usize), MailParseErr
     loop {
         match state {
             HeaderParseState::Initial => {
-                if c == b' ' {
-                    return Err(MailParseError::Generic(
-                        "Header cannot start with a space; it is \
-                                                        likely an overhanging line from a \
-                                                        previous header",
-                    ));
+                if c == b' ' || c == b'\t' {
+                    return Err(MailParseError::Generic(ErrorKind::SpaceBeforeHeader, 0));
                 };
                 state = HeaderParseState::Key;
                 continue;
@@ -308,8 +1183,15 @@ pub fn parse_header(raw_data: &[u8]) -> Result<(MailHeader, usize), MailParseErr
                 if c == b':' {
                     ix_key_end = Some(ix);
                     state = HeaderParseState::PreValue;
-                } else if c == b'\n' {
-                    return Err(MailParseError::Generic("Unexpected newline in header key"));
+                } else if (c == b'\n' || c == b'\r') && options.allow_flag_headers {
+                    // The whole line is the key, and the value is empty
+                    ix_key_end = Some(ix);
+                    ix_value_start = ix;
+                    ix_value_end = ix;
+                    state = HeaderParseState::Value;
+                    continue;
+                } else if c == b'\n' || c == b'\r' {
+                    return Err(MailParseError::Generic(ErrorKind::NewlineInKey, ix));
                 }
             }
             HeaderParseState::PreValue => {
@@ -323,10 +1205,22 @@ pub fn parse_header(raw_data: &[u8]) -> Result<(MailHeader, usize), MailParseErr
             HeaderParseState::Value => {
                 if c == b'\n' {
                     state = HeaderParseState::ValueNewline;
+                } else if c == b'\r' {
+                    state = HeaderParseState::ValueCarriageReturn;
                 } else {
                     ix_value_end = ix + 1;
                 }
             }
+            HeaderParseState::ValueCarriageReturn => {
+                // A CR followed by a LF is a single line break, but a bare CR
+                // is also treated as a line break.
+                if c == b'\n' {
+                    state = HeaderParseState::ValueNewline;
+                } else {
+                    state = HeaderParseState::ValueNewline;
+                    continue;
+                }
+            }
             HeaderParseState::ValueNewline => {
                 if c == b' ' || c == b'\t' {
                     state = HeaderParseState::Value;
@@ -342,10 +1236,28 @@ pub fn parse_header(raw_data: &[u8]) -> Result<(MailHeader, usize), MailParseErr
             Some(v) => *v,
         };
     }
+    if ix_key_end.is_none() && options.allow_flag_headers {
+        ix_key_end = Some(ix);
+        ix_value_start = ix;
+        ix_value_end = ix;
+    }
+    if let Some(max) = options.max_header_size {
+        if ix_value_end > max {
+            return Err(MailParseError::Generic(ErrorKind::HeaderTooLarge, 0));
+        }
+    }
     match ix_key_end {
         Some(v) => {
-            Ok((
-                MailHeader {
+            if options.strict_keys {
+                if v == 0 {
+                    return Err(MailParseError::Generic(ErrorKind::EmptyKey, 0));
+                }
+                if let Some(ix_bad) = raw_data[0..v].iter().position(|c| !is_key_char(*c)) {
+                    return Err(MailParseError::Generic(ErrorKind::InvalidKeyCharacter, ix_bad));
+                }
+            }
+            Ok((
+                MailHeader {
                     key: &raw_data[0..v],
                     value: &raw_data[ix_value_start..ix_value_end],
                 },
@@ -353,9 +1265,7 @@ pub fn parse_header(raw_data: &[u8]) -> Result<(MailHeader, usize), MailParseErr
             ))
         }
 
-        None => Err(MailParseError::Generic(
-            "Unable to determine end of the header key component",
-        )),
+        None => Err(MailParseError::Generic(ErrorKind::MissingColon, 0)),
     }
 }
 
@@ -396,12 +1306,177 @@ pub trait MailHeaderMap {
     ///         vec!["Value1".to_string(), "Value2".to_string()]);
     /// ```
     fn get_all_values(&self, key: &str) -> Result<Vec<String>, MailParseError>;
+
+    /// Look through the list of headers and return the value of the `n`th one
+    /// (counting from zero) that matches the provided key. It returns Ok(None)
+    /// if there are not that many matching headers. Header names are matched
+    /// case-insensitively, and only the matching header is decoded.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, MailHeaderMap};
+    ///     let headers = parse_mail(concat!(
+    ///             "Received: from b by c\n",
+    ///             "Subject: Test\n",
+    ///             "Received: from a by b").as_bytes())
+    ///         .unwrap().headers;
+    ///     assert_eq!(headers.get_nth_value("Received", 1).unwrap(), Some("from a by b".to_string()));
+    ///     assert_eq!(headers.get_nth_value("Received", 2).unwrap(), None);
+    /// ```
+    fn get_nth_value(&self, key: &str, n: usize) -> Result<Option<String>, MailParseError>;
+
+    /// Return the keys and values of all the headers, in the same order as
+    /// they appear in the message, and including any duplicates. This is
+    /// useful for logging or re-emitting the full set of headers.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, MailHeaderMap};
+    ///     let headers = parse_mail(concat!(
+    ///             "Key: Value1\n",
+    ///             "Other: =?utf-8?Q?Value2?=\n",
+    ///             "Key: Value3").as_bytes())
+    ///         .unwrap().headers;
+    ///     assert_eq!(headers.headers_as_pairs().unwrap(), vec![
+    ///         ("Key".to_string(), "Value1".to_string()),
+    ///         ("Other".to_string(), "Value2".to_string()),
+    ///         ("Key".to_string(), "Value3".to_string())]);
+    /// ```
+    fn headers_as_pairs(&self) -> Result<Vec<(String, String)>, MailParseError>;
+
+    /// Look through the list of headers and return the first one that matches
+    /// the provided key, without decoding its value. This is useful to only
+    /// pay for decoding the headers that are actually read, or to get at the
+    /// raw value bytes. Header names are matched case-insensitively.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, MailHeaderMap};
+    ///     let headers = parse_mail(concat!(
+    ///             "Subject: =?utf-8?Q?Test?=\n",
+    ///             "\n",
+    ///             "This is a test message").as_bytes())
+    ///         .unwrap().headers;
+    ///     let subject = headers.get_first_header("subject").unwrap();
+    ///     assert_eq!(subject.get_key().unwrap(), "Subject");
+    ///     assert_eq!(subject.get_value().unwrap(), "Test");
+    ///     assert!(headers.get_first_header("From").is_none());
+    /// ```
+    fn get_first_header(&self, key: &str) -> Option<&MailHeader<'_>>;
+
+    /// Look through the list of headers and return all the ones that match the
+    /// provided key, in the order in which they appear in the message, without
+    /// decoding their values. Header names are matched case-insensitively.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, MailHeaderMap};
+    ///     let headers = parse_mail(concat!(
+    ///             "Key: Value1\n",
+    ///             "Other: Value2\n",
+    ///             "key: Value3").as_bytes())
+    ///         .unwrap().headers;
+    ///     let keys: Vec<_> = headers.get_all_headers("KEY").iter()
+    ///         .map(|h| h.get_key().unwrap()).collect();
+    ///     assert_eq!(keys, vec!["Key", "key"]);
+    /// ```
+    fn get_all_headers(&self, key: &str) -> Vec<&MailHeader<'_>>;
+
+    /// Parse the addresses in all the headers that match the provided key,
+    /// using `addrparse_header`, and return them in the order in which they
+    /// appear in the message. Returns an empty vector if no matching headers
+    /// were found. Header names are matched case-insensitively.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, MailAddr, MailHeaderMap, SingleInfo};
+    ///     let headers = parse_mail(concat!(
+    ///             "Resent-To: John Doe <john@example.com>\n",
+    ///             "Resent-To: jane@example.com\n",
+    ///             "\n",
+    ///             "This is a test message").as_bytes())
+    ///         .unwrap().headers;
+    ///     let addrs = headers.get_addresses("Resent-To").unwrap();
+    ///     assert_eq!(addrs[1], MailAddr::Single(SingleInfo {
+    ///         display_name: None,
+    ///         addr: "jane@example.com".to_string(),
+    ///     }));
+    ///     assert!(headers.get_addresses("Resent-Cc").unwrap().is_empty());
+    /// ```
+    fn get_addresses(&self, key: &str) -> Result<Vec<MailAddr>, MailParseError> {
+        let mut addrs = Vec::new();
+        for header in self.get_all_headers(key) {
+            addrs.extend(addrparse_header(header)?);
+        }
+        Ok(addrs)
+    }
+
+    /// Parse the addresses in the From header(s), as with `get_addresses`.
+    fn get_from(&self) -> Result<Vec<MailAddr>, MailParseError> {
+        self.get_addresses("From")
+    }
+
+    /// Parse the addresses in the To header(s), as with `get_addresses`.
+    fn get_to(&self) -> Result<Vec<MailAddr>, MailParseError> {
+        self.get_addresses("To")
+    }
+
+    /// Parse the addresses in the Cc header(s), as with `get_addresses`.
+    fn get_cc(&self) -> Result<Vec<MailAddr>, MailParseError> {
+        self.get_addresses("Cc")
+    }
+
+    /// Parse the addresses in the Reply-To header(s), as with `get_addresses`.
+    fn get_reply_to(&self) -> Result<Vec<MailAddr>, MailParseError> {
+        self.get_addresses("Reply-To")
+    }
+
+    /// Parse the address in the Sender header, as with `get_addresses`.
+    fn get_sender(&self) -> Result<Vec<MailAddr>, MailParseError> {
+        self.get_addresses("Sender")
+    }
+
+    /// Parse the address in the Return-Path header, as with `get_addresses`.
+    /// The null return path `<>` results in a single address with an empty
+    /// `addr`.
+    fn get_return_path(&self) -> Result<Vec<MailAddr>, MailParseError> {
+        self.get_addresses("Return-Path")
+    }
+
+    /// Get the name to show for the sender of the message, e.g. in a
+    /// notification. This is the decoded display name of the first mailbox
+    /// in the From header(s), or the address itself if that mailbox has no
+    /// display name. For a group, its first mailbox is used. Returns Ok(None)
+    /// if there is no mailbox in the From header(s).
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, MailHeaderMap};
+    ///     let headers = parse_mail(concat!(
+    ///             "From: =?utf-8?Q?John_Doe?= <john@example.com>, jane@example.com\n",
+    ///             "\n",
+    ///             "This is a test message").as_bytes())
+    ///         .unwrap().headers;
+    ///     assert_eq!(headers.get_from_display_name().unwrap(), Some("John Doe".to_string()));
+    ///     let headers = parse_mail(b"From: <john@example.com>\n\nBody").unwrap().headers;
+    ///     assert_eq!(headers.get_from_display_name().unwrap(), Some("john@example.com".to_string()));
+    /// ```
+    fn get_from_display_name(&self) -> Result<Option<String>, MailParseError> {
+        let first = self.get_from()?.into_iter().find_map(|addr| match addr {
+            MailAddr::Single(info) => Some(info),
+            MailAddr::Group(group) => group.addrs.into_iter().next(),
+        });
+        Ok(first.map(|info| match info.display_name {
+            Some(name) if !name.trim().is_empty() => name,
+            _ => info.addr,
+        }))
+    }
 }
 
 impl<'a> MailHeaderMap for [MailHeader<'a>] {
     fn get_first_value(&self, key: &str) -> Result<Option<String>, MailParseError> {
         for x in self {
-            if x.get_key()?.eq_ignore_ascii_case(key) {
+            if x.key_is(key) {
                 return x.get_value().map(Some);
             }
         }
@@ -411,12 +1486,90 @@ impl<'a> MailHeaderMap for [MailHeader<'a>] {
     fn get_all_values(&self, key: &str) -> Result<Vec<String>, MailParseError> {
         let mut values: Vec<String> = Vec::new();
         for x in self {
-            if x.get_key()?.eq_ignore_ascii_case(key) {
+            if x.key_is(key) {
                 values.push(x.get_value()?);
             }
         }
         Ok(values)
     }
+
+    fn get_nth_value(&self, key: &str, n: usize) -> Result<Option<String>, MailParseError> {
+        match self.iter().filter(|x| x.key_is(key)).nth(n) {
+            Some(x) => x.get_value().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn headers_as_pairs(&self) -> Result<Vec<(String, String)>, MailParseError> {
+        self.iter()
+            .map(|x| Ok((x.get_key()?, x.get_value()?)))
+            .collect()
+    }
+
+    fn get_first_header(&self, key: &str) -> Option<&MailHeader<'_>> {
+        self.iter().find(|x| x.key_is(key))
+    }
+
+    fn get_all_headers(&self, key: &str) -> Vec<&MailHeader<'_>> {
+        self.iter().filter(|x| x.key_is(key)).collect()
+    }
+}
+
+/// Options that control how strictly the parsing functions interpret their
+/// input. The default options are lenient, to handle as much real-world email
+/// data as possible; individual options can be turned on to reject input that
+/// is malformed in specific ways. The options apply to every nested part,
+/// including the message encapsulated in a message/rfc822 part.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// If set, a header block that runs up to the end of the input without
+    /// being terminated by a blank line results in an `UnterminatedHeaders`
+    /// error. This distinguishes a truncated message from one that has a
+    /// legitimately empty body. By default this is not an error.
+    pub require_header_terminator: bool,
+    /// If set, header keys must be made up of the characters allowed by RFC
+    /// 5322, i.e. printable US-ASCII characters other than the colon (bytes
+    /// 33 to 126, excluding 58). An empty key results in an `EmptyKey` error,
+    /// and a key containing any other byte (a space, tab, control character
+    /// or non-ASCII byte) results in an `InvalidKeyCharacter` error at the
+    /// offset of that byte. By default any key is accepted, with surrounding
+    /// whitespace being trimmed by `MailHeader::get_key`.
+    pub strict_keys: bool,
+    /// If set, a line in the header block that has no colon (and is not a
+    /// continuation line) is taken to be a "flag" header: a header whose key
+    /// is the whole line and whose value is empty. Parsing then continues
+    /// with the next line, so that one malformed line doesn't prevent reading
+    /// the rest of the headers. By default such a line results in a
+    /// `NewlineInKey` error (or `MissingColon` at the end of the input).
+    pub allow_flag_headers: bool,
+    /// The maximum depth to which `parse_mail_with_options` parses nested
+    /// parts. The top-level message is at depth 0, and each multipart part
+    /// or encapsulated message/rfc822 message is one deeper than its parent.
+    /// A part nested more deeply than this results in a `NestingTooDeep`
+    /// error at the offset of that part, rather than the parser recursing
+    /// until it runs out of stack on maliciously crafted input. The default
+    /// is 100, which is far deeper than any legitimate message.
+    pub max_depth: usize,
+    /// If set, the maximum size in bytes of a single header, from the start
+    /// of its key to the end of its value. Line breaks within a folded value
+    /// count towards the size, but the one that terminates the header does
+    /// not. A larger header results in a `HeaderTooLarge` error at the
+    /// offset of the header, which lets callers cap the memory used by
+    /// `MailHeader::get_value` on untrusted input. By default the size of
+    /// headers is unlimited.
+    pub max_header_size: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            require_header_terminator: false,
+            strict_keys: false,
+            allow_flag_headers: false,
+            max_depth: 100,
+            max_header_size: None,
+        }
+    }
 }
 
 /// Parses all the headers from the raw data given.
@@ -429,6 +1582,10 @@ impl<'a> MailHeaderMap for [MailHeader<'a>] {
 /// returns the index at which the message body is expected to start. If you
 /// just care about the headers, you can ignore the second component of the
 /// returned tuple.
+/// Each line, including the blank line, may be terminated by a CRLF, a bare LF
+/// or a bare CR, independently of the other lines. The one ambiguity is a
+/// bare CR followed by a LF, which is always taken to be a single CRLF line
+/// break.
 /// Error values are returned if there was some sort of parsing error.
 ///
 /// # Examples
@@ -442,11 +1599,61 @@ impl<'a> MailHeaderMap for [MailHeader<'a>] {
 ///     assert_eq!(headers[1].get_key().unwrap(), "From");
 ///     assert_eq!(headers.get_first_value("To").unwrap(), Some("you@yourself.com".to_string()));
 /// ```
-pub fn parse_headers(raw_data: &[u8]) -> Result<(Vec<MailHeader>, usize), MailParseError> {
+pub fn parse_headers(raw_data: &[u8]) -> Result<(Vec<MailHeader<'_>>, usize), MailParseError> {
+    parse_headers_with_options(raw_data, &ParseOptions::default())
+}
+
+/// Parses all the headers from the raw data given, as with `parse_headers`,
+/// but returns the raw bytes of the header block instead of the index of the
+/// body. The returned slice covers the headers up to and including the blank
+/// line that terminates them (whether that is a CRLF, LF or bare CR), so the
+/// body starts right after it, at `raw_data[block.len()..]`. If the headers
+/// are not terminated by a blank line, the slice covers all of `raw_data`.
+///
+/// # Examples
+/// ```
+///     use mailparse::parse_headers_with_span;
+///     let raw = b"Subject: Test\r\nTo: you\r\n\r\nBody";
+///     let (headers, block) = parse_headers_with_span(raw).unwrap();
+///     assert_eq!(headers.len(), 2);
+///     assert_eq!(block, b"Subject: Test\r\nTo: you\r\n\r\n");
+///     assert_eq!(&raw[block.len()..], b"Body");
+/// ```
+pub fn parse_headers_with_span(
+    raw_data: &[u8],
+) -> Result<(Vec<MailHeader<'_>>, &[u8]), MailParseError> {
+    let (headers, ix_body) = parse_headers(raw_data)?;
+    Ok((headers, &raw_data[..ix_body]))
+}
+
+/// Parses all the headers from the raw data given, as with `parse_headers`,
+/// but using the given options to control how strict the parsing is.
+///
+/// # Examples
+/// ```
+///     use mailparse::{parse_headers_with_options, ErrorKind, MailParseError, ParseOptions};
+///     let options = ParseOptions { require_header_terminator: true, ..Default::default() };
+///     let (headers, ix_body) = parse_headers_with_options(
+///             b"Subject: Test\n\n", &options)
+///         .unwrap();
+///     assert_eq!(headers.len(), 1);
+///     assert_eq!(ix_body, 15);
+///     match parse_headers_with_options(b"Subject: Test\n", &options) {
+///         Err(MailParseError::Generic(ErrorKind::UnterminatedHeaders, 14)) => (),
+///         _ => panic!("Truncated header block was not detected"),
+///     }
+/// ```
+pub fn parse_headers_with_options<'a>(
+    raw_data: &'a [u8],
+    options: &ParseOptions,
+) -> Result<(Vec<MailHeader<'a>>, usize), MailParseError> {
     let mut headers: Vec<MailHeader> = Vec::new();
     let mut ix = 0;
     loop {
         if ix >= raw_data.len() {
+            if options.require_header_terminator {
+                return Err(MailParseError::Generic(ErrorKind::UnterminatedHeaders, ix));
+            }
             break;
         } else if raw_data[ix] == b'\n' {
             ix += 1;
@@ -454,15 +1661,13 @@ pub fn parse_headers(raw_data: &[u8]) -> Result<(Vec<MailHeader>, usize), MailPa
         } else if raw_data[ix] == b'\r' {
             if ix + 1 < raw_data.len() && raw_data[ix + 1] == b'\n' {
                 ix += 2;
-                break;
             } else {
-                return Err(MailParseError::Generic(
-                    "Headers were followed by an unexpected lone \
-                                                    CR character!",
-                ));
+                ix += 1;
             }
+            break;
         }
-        let (header, ix_next) = parse_header(&raw_data[ix..])?;
+        let (header, ix_next) =
+            parse_header_with_options(&raw_data[ix..], options).map_err(|e| e.offset_by(ix))?;
         headers.push(header);
         ix += ix_next;
     }
@@ -472,16 +1677,20 @@ pub fn parse_headers(raw_data: &[u8]) -> Result<(Vec<MailHeader>, usize), MailPa
 /// A struct to hold a more structured representation of the Content-Type header.
 /// This is provided mostly as a convenience since this metadata is usually
 /// needed to interpret the message body properly.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParsedContentType {
     /// The type of the data, for example "text/plain" or "application/pdf".
     pub mimetype: String,
     /// The charset used to decode the raw byte data, for example "iso-8859-1"
-    /// or "utf-8".
-    pub charset: String,
+    /// or "utf-8". This is None if the Content-Type didn't specify a charset,
+    /// so that callers can apply their own default; `ParsedMail::get_body`
+    /// uses "us-ascii" in that case.
+    pub charset: Option<String>,
     /// The additional params of Content-Type, e.g. filename and boundary. The
     /// keys in the map will be lowercased, and the values will have any
-    /// enclosing quotes stripped.
+    /// enclosing quotes stripped. The name and filename values are decoded
+    /// if they are made up of RFC 2047 encoded-words (as some mailers send
+    /// non-ASCII filenames); other values are left as they are.
     pub params: BTreeMap<String, String>,
 }
 
@@ -489,17 +1698,141 @@ impl Default for ParsedContentType {
     fn default() -> Self {
         ParsedContentType {
             mimetype: "text/plain".to_string(),
-            charset: "us-ascii".to_string(),
+            charset: None,
             params: BTreeMap::new(),
         }
     }
 }
 
-/// Helper method to parse a header value as a Content-Type header. Note that
-/// the returned object's `params` map will contain a charset key if a charset
-/// was explicitly specified in the header; otherwise the `params` map will not
-/// contain a charset key. Regardless, the `charset` field will contain a
-/// charset - either the one explicitly specified or the default of "us-ascii".
+/// The top-level media types that a Content-Type mimetype can have, as
+/// registered with IANA. The `Other` value holds any other top-level type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MimeKind {
+    /// The "text" top-level type, e.g. "text/plain".
+    Text,
+    /// The "multipart" top-level type, e.g. "multipart/alternative".
+    Multipart,
+    /// The "message" top-level type, e.g. "message/rfc822".
+    Message,
+    /// The "image" top-level type, e.g. "image/png".
+    Image,
+    /// The "audio" top-level type, e.g. "audio/mpeg".
+    Audio,
+    /// The "video" top-level type, e.g. "video/mp4".
+    Video,
+    /// The "application" top-level type, e.g. "application/pdf".
+    Application,
+    /// Any other top-level type. The string will be lowercased.
+    Other(String),
+}
+
+impl ParsedContentType {
+    /// Parse the Content-Type out of a list of headers, e.g. those of a
+    /// message subpart. The first Content-Type header is used if there are
+    /// several, and if there is none the default (text/plain with no charset)
+    /// is returned. This is how `parse_mail` fills in `ParsedMail::ctype`.
+    ///
+    /// The value is decoded as by `MailHeader::get_value` before it is
+    /// parsed. Some broken mailers encode the whole value (or just the
+    /// mimetype) as RFC 2047 encoded-words, so this decoding also takes care
+    /// of an encoded-word at the start of the value that is directly followed
+    /// by e.g. a `;`, which `get_value` leaves alone. Also, some mailers fold
+    /// long values in the middle of a quoted parameter value such as the
+    /// boundary, so a line break inside a quoted string is removed together
+    /// with the whitespace that starts the next line, rather than being
+    /// unfolded into a space. The boundary parameter is never decoded, as it
+    /// has to match the delimiters in the body exactly.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_headers, ParsedContentType};
+    ///     let (headers, _) = parse_headers(b"Content-Type: text/html; charset=utf-8\n\n").unwrap();
+    ///     let ctype = ParsedContentType::from_headers(&headers).unwrap();
+    ///     assert_eq!(ctype.mimetype, "text/html");
+    ///     let (headers, _) = parse_headers(b"Subject: test\n\n").unwrap();
+    ///     assert_eq!(ParsedContentType::from_headers(&headers).unwrap().mimetype, "text/plain");
+    /// ```
+    pub fn from_headers(headers: &[MailHeader]) -> Result<ParsedContentType, MailParseError> {
+        let header = match headers.get_first_header("Content-Type") {
+            Some(header) => header,
+            None => return Ok(ParsedContentType::default()),
+        };
+        let joined = join_quoted_folds(header.value);
+        let raw = joined.as_deref().unwrap_or(header.value);
+        let value = MailHeader { key: header.key, value: raw }.get_value()?;
+        let mut ctype = match decode_leading_word(&value) {
+            Some(decoded) => parse_content_type(&decoded),
+            None => parse_content_type(&value),
+        };
+        // The boundary must match the delimiters in the body exactly, so it
+        // is taken from the value without decoding any encoded-words, as it
+        // may contain text that looks like one
+        if ctype.params.contains_key("boundary") {
+            let raw = encoding::all::ISO_8859_1.decode(raw, encoding::DecoderTrap::Replace)?;
+            if let Some(boundary) = parse_content_type_ref(&raw).boundary {
+                ctype.params.insert("boundary".to_string(), boundary.to_string());
+            }
+        }
+        Ok(ctype)
+    }
+
+    /// Classify the mimetype by its top-level type. The full mimetype string
+    /// is still available in the `mimetype` field for subtype details.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_content_type, MimeKind};
+    ///     assert_eq!(parse_content_type("text/html").kind(), MimeKind::Text);
+    ///     assert_eq!(parse_content_type("Multipart/Mixed; boundary=x").kind(), MimeKind::Multipart);
+    ///     assert_eq!(parse_content_type("model/vrml").kind(), MimeKind::Other("model".to_string()));
+    /// ```
+    pub fn kind(&self) -> MimeKind {
+        match self.top_level_type() {
+            "text" => MimeKind::Text,
+            "multipart" => MimeKind::Multipart,
+            "message" => MimeKind::Message,
+            "image" => MimeKind::Image,
+            "audio" => MimeKind::Audio,
+            "video" => MimeKind::Video,
+            "application" => MimeKind::Application,
+            other => MimeKind::Other(other.to_string()),
+        }
+    }
+
+    /// Get the top-level type of the mimetype, i.e. the part before the `/`.
+    /// If the mimetype has no `/`, this is the whole mimetype.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_content_type;
+    ///     assert_eq!(parse_content_type("multipart/alternative").top_level_type(), "multipart");
+    ///     assert_eq!(parse_content_type("bogus").top_level_type(), "bogus");
+    /// ```
+    pub fn top_level_type(&self) -> &str {
+        self.mimetype.split_once('/').map_or(&self.mimetype[..], |(top_level, _)| top_level)
+    }
+
+    /// Get the subtype of the mimetype, i.e. the part after the `/`, such as
+    /// "alternative" or "mixed" for multipart types. If the mimetype has no
+    /// `/`, this is empty.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_content_type;
+    ///     assert_eq!(parse_content_type("Multipart/Related; type=text/html").subtype(), "related");
+    ///     assert_eq!(parse_content_type("bogus").subtype(), "");
+    /// ```
+    pub fn subtype(&self) -> &str {
+        self.mimetype.split_once('/').map_or("", |(_, subtype)| subtype)
+    }
+}
+
+/// Helper method to parse a header value as a Content-Type header. If a charset
+/// was explicitly specified in the header, it is available in the `charset`
+/// field and the returned object's `params` map will contain a charset key;
+/// otherwise the `charset` field is None and the `params` map will not contain
+/// a charset key. If a parameter is given more than once, the first
+/// occurrence is used and the others are ignored.
 ///
 /// # Examples
 /// ```
@@ -509,7 +1842,7 @@ impl Default for ParsedContentType {
 ///         .unwrap();
 ///     let ctype = parse_content_type(&parsed.get_value().unwrap());
 ///     assert_eq!(ctype.mimetype, "text/html");
-///     assert_eq!(ctype.charset, "foo");
+///     assert_eq!(ctype.charset, Some("foo".to_string()));
 ///     assert_eq!(ctype.params.get("boundary"), Some(&"quotes_are_removed".to_string()));
 ///     assert_eq!(ctype.params.get("charset"), Some(&"foo".to_string()));
 /// ```
@@ -518,7 +1851,7 @@ impl Default for ParsedContentType {
 ///     let (parsed, _) = parse_header(b"Content-Type: bogus").unwrap();
 ///     let ctype = parse_content_type(&parsed.get_value().unwrap());
 ///     assert_eq!(ctype.mimetype, "bogus");
-///     assert_eq!(ctype.charset, "us-ascii");
+///     assert_eq!(ctype.charset, None);
 ///     assert_eq!(ctype.params.get("boundary"), None);
 ///     assert_eq!(ctype.params.get("charset"), None);
 /// ```
@@ -527,18 +1860,14 @@ impl Default for ParsedContentType {
 ///     let (parsed, _) = parse_header(br#"Content-Type: application/octet-stream;name="=?utf8?B?6L+O5ai255m95a+M576O?=";charset="utf8""#).unwrap();
 ///     let ctype = parse_content_type(&parsed.get_value().unwrap());
 ///     assert_eq!(ctype.mimetype, "application/octet-stream");
-///     assert_eq!(ctype.charset, "utf8");
+///     assert_eq!(ctype.charset, Some("utf8".to_string()));
 ///     assert_eq!(ctype.params.get("boundary"), None);
 ///     assert_eq!(ctype.params.get("name"), Some(&"迎娶白富美".to_string()));
 /// ```
 pub fn parse_content_type(header: &str) -> ParsedContentType {
     let params = parse_param_content(header);
     let mimetype = params.value.to_lowercase();
-    let charset = params
-        .params
-        .get("charset")
-        .cloned()
-        .unwrap_or_else(|| "us-ascii".to_string());
+    let charset = params.params.get("charset").cloned();
 
     ParsedContentType {
         mimetype,
@@ -547,15 +1876,82 @@ pub fn parse_content_type(header: &str) -> ParsedContentType {
     }
 }
 
+/// A borrowing version of `ParsedContentType`, as returned by
+/// `parse_content_type_ref`. The fields are slices of the header value, so
+/// they are not lowercased; compare them case-insensitively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsedContentTypeRef<'a> {
+    /// The type of the data, for example "text/plain" or "Application/PDF".
+    pub mimetype: &'a str,
+    /// The charset parameter, if there is one.
+    pub charset: Option<&'a str>,
+    /// The boundary parameter, if there is one.
+    pub boundary: Option<&'a str>,
+}
+
+impl<'a> ParsedContentTypeRef<'a> {
+    /// Check if the mimetype is the given one, ignoring ASCII case.
+    pub fn mimetype_is(&self, mimetype: &str) -> bool {
+        self.mimetype.eq_ignore_ascii_case(mimetype)
+    }
+}
+
+/// Parse a header value as a Content-Type header, as with
+/// `parse_content_type`, but without allocating. Only the mimetype and the
+/// charset and boundary parameters are extracted, and they are returned as
+/// slices of the input: their surrounding whitespace and quotes are removed,
+/// but they are not lowercased and encoded-words in them are not decoded. As
+/// with `parse_content_type`, the first occurrence of a duplicated parameter
+/// is used.
+///
+/// # Examples
+/// ```
+///     use mailparse::parse_content_type_ref;
+///     let ctype = parse_content_type_ref("Multipart/Mixed; Boundary=\"abc\"; charset=UTF-8");
+///     assert_eq!(ctype.mimetype, "Multipart/Mixed");
+///     assert!(ctype.mimetype_is("multipart/mixed"));
+///     assert_eq!(ctype.boundary, Some("abc"));
+///     assert_eq!(ctype.charset, Some("UTF-8"));
+/// ```
+pub fn parse_content_type_ref(header: &str) -> ParsedContentTypeRef<'_> {
+    let mut tokens = header.split(';');
+    let mut ctype = ParsedContentTypeRef {
+        mimetype: tokens.next().unwrap_or("").trim(),
+        charset: None,
+        boundary: None,
+    };
+    for kv in tokens {
+        if let Some(idx) = kv.find('=') {
+            let key = kv[0..idx].trim();
+            let param = if key.eq_ignore_ascii_case("charset") {
+                &mut ctype.charset
+            } else if key.eq_ignore_ascii_case("boundary") {
+                &mut ctype.boundary
+            } else {
+                continue;
+            };
+            if param.is_none() {
+                let mut value = kv[idx + 1..].trim();
+                if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                    value = &value[1..value.len() - 1];
+                }
+                *param = Some(value);
+            }
+        }
+    }
+    ctype
+}
+
 /// The possible disposition types in a Content-Disposition header. A more
 /// comprehensive list of IANA-recognized types can be found at
 /// https://www.iana.org/assignments/cont-disp/cont-disp.xhtml. This library
 /// only enumerates the types most commonly found in email messages, and
 /// provides the `Extension` value for holding all other types.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum DispositionType {
     /// Default value, indicating the content is to be displayed inline as
     /// part of the enclosing document.
+    #[default]
     Inline,
     /// A disposition indicating the content is not meant for inline display,
     /// but whose content can be accessed for use.
@@ -566,12 +1962,6 @@ pub enum DispositionType {
     Extension(String),
 }
 
-impl Default for DispositionType {
-    fn default() -> Self {
-        DispositionType::Inline
-    }
-}
-
 /// Convert the string represented disposition type to enum.
 fn parse_disposition_type(disposition: &str) -> DispositionType {
     match &disposition.to_lowercase()[..] {
@@ -582,17 +1972,105 @@ fn parse_disposition_type(disposition: &str) -> DispositionType {
     }
 }
 
+/// How a message part should be presented when rendering the message, as
+/// determined by `ParsedMail::disposition_kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispositionKind {
+    /// The part is (part of) the displayable body of the message.
+    Body,
+    /// The part is an image (or other media) to be displayed inline, e.g.
+    /// one referenced from an HTML body by its Content-ID.
+    Inline,
+    /// The part is an attachment to be offered for download.
+    Attachment,
+}
+
+/// The Content-Transfer-Encoding of a message part, as returned by
+/// `ParsedMail::transfer_encoding`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TransferEncoding {
+    /// The "7bit" identity encoding. This is the default if there is no
+    /// Content-Transfer-Encoding header.
+    #[default]
+    SevenBit,
+    /// The "8bit" identity encoding.
+    EightBit,
+    /// The "binary" identity encoding.
+    Binary,
+    /// The "base64" encoding.
+    Base64,
+    /// The "quoted-printable" encoding.
+    QuotedPrintable,
+    /// The non-standard "x-uuencode" encoding (or one of its aliases
+    /// "x-uue", "uuencode" and "uue").
+    Uuencode,
+    /// Any other encoding, which this library cannot decode. The string is
+    /// lowercased.
+    Other(String),
+}
+
+impl TransferEncoding {
+    /// Check if this encoding leaves the data unchanged.
+    fn is_identity(&self) -> bool {
+        matches!(
+            self,
+            TransferEncoding::SevenBit | TransferEncoding::EightBit | TransferEncoding::Binary
+        )
+    }
+}
+
+/// Convert a Content-Transfer-Encoding header value to the enum. Only the
+/// first token of the value is used, so that surrounding whitespace and
+/// trailing comments such as `base64 (standard)` are ignored.
+fn parse_transfer_encoding(value: &str) -> TransferEncoding {
+    let token = value
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .find(|token| !token.is_empty())
+        .unwrap_or("")
+        .trim_matches('"')
+        .to_lowercase();
+    match &token[..] {
+        "7bit" => TransferEncoding::SevenBit,
+        "8bit" => TransferEncoding::EightBit,
+        "binary" => TransferEncoding::Binary,
+        "base64" => TransferEncoding::Base64,
+        "quoted-printable" => TransferEncoding::QuotedPrintable,
+        "x-uuencode" | "x-uue" | "uuencode" | "uue" => TransferEncoding::Uuencode,
+        _ => TransferEncoding::Other(token),
+    }
+}
+
+/// Check if this library can decode bodies with the given
+/// Content-Transfer-Encoding, i.e. if `ParsedMail::get_body_raw` and friends
+/// will not fail with an `UnsupportedTransferEncoding` error for it. The name
+/// is interpreted as the value of a Content-Transfer-Encoding header would
+/// be, so it is matched case-insensitively and may be surrounded by
+/// whitespace.
+///
+/// # Examples
+/// ```
+///     use mailparse::is_transfer_encoding_supported;
+///     assert!(is_transfer_encoding_supported("Base64"));
+///     assert!(is_transfer_encoding_supported("x-uuencode"));
+///     assert!(!is_transfer_encoding_supported("x-gzip64"));
+/// ```
+pub fn is_transfer_encoding_supported(name: &str) -> bool {
+    !matches!(parse_transfer_encoding(name), TransferEncoding::Other(_))
+}
+
 /// A struct to hold a more structured representation of the Content-Disposition header.
 /// This is provided mostly as a convenience since this metadata is usually
 /// needed to interpret the message body properly.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ParsedContentDisposition {
     /// The disposition type of the Content-Disposition header. If this
     /// is an extension type, the string will be lowercased.
     pub disposition: DispositionType,
     /// The additional params of Content-Disposition, e.g. filename. The
     /// keys in the map will be lowercased, and the values will have any
-    /// enclosing quotes stripped.
+    /// enclosing quotes stripped. The name and filename values are decoded
+    /// if they are made up of RFC 2047 encoded-words (as some mailers send
+    /// non-ASCII filenames); other values are left as they are.
     pub params: BTreeMap<String, String>,
 }
 
@@ -629,19 +2107,175 @@ pub struct ParsedMail<'a> {
     /// The headers for the message (or message subpart).
     pub headers: Vec<MailHeader<'a>>,
     /// The Content-Type information for the message (or message subpart).
+    /// If there is no Content-Type header, this is text/plain, except for
+    /// the parts of a multipart/digest, which default to message/rfc822 as
+    /// described in RFC 2046 section 5.1.5.
     pub ctype: ParsedContentType,
     /// The raw bytes that make up the body of the message (or message subpart).
     body: &'a [u8],
     /// The subparts of this message or subpart. This vector is only non-empty
-    /// if ctype.mimetype starts with "multipart/".
+    /// if ctype.mimetype starts with "multipart/", or if ctype.mimetype is
+    /// "message/rfc822" or "message/global", in which case it holds the
+    /// single encapsulated message. If the body of such a part is not a valid
+    /// message (e.g. because it has no headers), it is left empty and the
+    /// body can only be read as-is. However, if the body fails one of the
+    /// checks enabled in `ParseOptions`, that is still an error.
     pub subparts: Vec<ParsedMail<'a>>,
+    /// Whether this is a multipart message whose closing boundary is missing.
+    truncated: bool,
 }
 
 impl<'a> ParsedMail<'a> {
+    /// Get the headers for the message (or message subpart).
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Subject: test\n",
+    ///             "\n",
+    ///             "This is the body").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_headers()[0].get_key().unwrap(), "Subject");
+    /// ```
+    pub fn get_headers(&self) -> &[MailHeader<'a>] {
+        &self.headers
+    }
+
+    /// Get the parsed Content-Type of the message (or message subpart). This
+    /// is the same as the `ctype` field, and is the default of text/plain if
+    /// there was no Content-Type header.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(b"Content-Type: text/html\n\n<p>Hi</p>").unwrap();
+    ///     assert_eq!(p.get_content_type().mimetype, "text/html");
+    ///     let p = parse_mail(b"Subject: test\n\nHi").unwrap();
+    ///     assert_eq!(p.get_content_type().mimetype, "text/plain");
+    /// ```
+    pub fn get_content_type(&self) -> &ParsedContentType {
+        &self.ctype
+    }
+
+    /// Get the raw bytes that make up the body of the message (or message
+    /// subpart), exactly as they appear in the input. Unlike `get_body_raw`,
+    /// this does not unapply the Content-Transfer-Encoding.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Transfer-Encoding: base64\n",
+    ///             "\n",
+    ///             "aGVsbG8=").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_body_encoded(), b"aGVsbG8=");
+    ///     assert_eq!(p.get_body_raw().unwrap(), b"hello");
+    /// ```
+    pub fn get_body_encoded(&self) -> &'a [u8] {
+        self.body
+    }
+
+    /// Get the subparts of this message or subpart. See the documentation
+    /// on the `subparts` field for when this is non-empty.
+    pub fn get_subparts(&self) -> &[ParsedMail<'a>] {
+        &self.subparts
+    }
+
+    /// Returns true if this is a multipart message (or message subpart) that
+    /// was actually split into parts. Unlike checking for a `multipart/`
+    /// mimetype, this is false for a multipart part that is missing its
+    /// boundary parameter, or whose boundary doesn't appear in the body, as
+    /// such a part has no subparts. An encapsulated message/rfc822 message
+    /// has a subpart, but is not multipart.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/mixed; boundary=b\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "\n",
+    ///             "part 1\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     assert!(p.is_multipart());
+    ///     assert!(!p.subparts[0].is_multipart());
+    ///     let p = parse_mail(b"Content-Type: multipart/mixed\n\nno boundary").unwrap();
+    ///     assert!(!p.is_multipart());
+    /// ```
+    pub fn is_multipart(&self) -> bool {
+        self.ctype.mimetype.starts_with("multipart/") && !self.subparts.is_empty()
+    }
+
+    /// Returns true if this is a multipart message (or message subpart) that
+    /// ended without its closing boundary delimiter, which usually means the
+    /// message was truncated. The parts that were found before the end of the
+    /// data are still available in `subparts`, but the last of them may be
+    /// incomplete. A body that doesn't contain any boundary delimiter at all
+    /// has no parts, so it doesn't count as truncated. Note that this only
+    /// applies to this part; use `is_truncated` on the subparts to check them
+    /// as well.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/mixed; boundary=b\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "\n",
+    ///             "part 1\n",
+    ///             "--b\n",
+    ///             "\n",
+    ///             "part 2 got cut o").as_bytes())
+    ///         .unwrap();
+    ///     assert!(p.is_truncated());
+    ///     assert_eq!(p.subparts.len(), 2);
+    ///     assert_eq!(p.subparts[1].get_body().unwrap(), "part 2 got cut o");
+    /// ```
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Make a copy of this message (or message subpart) that owns all of its
+    /// data, so that it can outlive the raw input buffer that was parsed.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, ParsedMailOwned};
+    ///     let owned: ParsedMailOwned = {
+    ///         let raw = b"Subject: test\n\nThis is the body".to_vec();
+    ///         parse_mail(&raw).unwrap().to_owned()
+    ///     };
+    ///     assert_eq!(owned.as_parsed_mail().get_body().unwrap(), "This is the body");
+    /// ```
+    pub fn to_owned(&self) -> ParsedMailOwned {
+        ParsedMailOwned {
+            headers: self
+                .headers
+                .iter()
+                .map(|h| (h.key.to_vec(), h.value.to_vec()))
+                .collect(),
+            ctype: self.ctype.clone(),
+            body: self.body.to_vec(),
+            subparts: self.subparts.iter().map(ParsedMail::to_owned).collect(),
+            truncated: self.truncated,
+        }
+    }
+
     /// Get the body of the message as a Rust string. This function tries to
     /// unapply the Content-Transfer-Encoding if there is one, and then converts
     /// the result into a Rust UTF-8 string using the charset in the Content-Type
-    /// (or "us-ascii" if the charset was missing or not recognized).
+    /// (or "us-ascii" if the charset was missing or not recognized). Bytes that
+    /// are invalid in the charset are replaced by U+FFFD. As an exception, an
+    /// empty charset or a placeholder such as `x-unknown` that some
+    /// generated mail declares is taken to mean UTF-8. If the body starts with
+    /// a UTF-8, UTF-16LE or UTF-16BE byte order mark, the encoding that it
+    /// indicates is used regardless of the charset, and the byte order mark is
+    /// left out of the result.
     ///
     /// # Examples
     /// ```
@@ -652,53 +2286,469 @@ impl<'a> ParsedMail<'a> {
     ///             "This is the body").as_bytes())
     ///         .unwrap();
     ///     assert_eq!(p.get_body().unwrap(), "This is the body");
+    ///     let p = parse_mail(b"Content-Type: text/plain; charset=utf-8\n\n\xff\xfeH\x00i\x00").unwrap();
+    ///     assert_eq!(p.get_body().unwrap(), "Hi");
     /// ```
     pub fn get_body(&self) -> Result<String, MailParseError> {
-        let decoded = self.get_body_raw()?;
-        let charset_conv = encoding::label::encoding_from_whatwg_label(&self.ctype.charset)
-            .unwrap_or(encoding::all::ASCII);
-        charset_conv.decode(&decoded, encoding::DecoderTrap::Replace)
-            .map_err(|e| e.into())
+        self.get_body_with_trap(encoding::DecoderTrap::Replace)
     }
 
-    /// Get the body of the message as a Rust Vec<u8>. This function tries to
-    /// unapply the Content-Transfer-Encoding if there is one, but won't do
-    /// any charset decoding.
+    /// Get the body of the message as a Rust string, as with `get_body`, but
+    /// using the given trap to handle bytes that are invalid in the charset.
+    /// `get_body` uses `DecoderTrap::Replace`; passing `DecoderTrap::Strict`
+    /// instead causes an `EncodingError` to be returned for malformed data.
     ///
     /// # Examples
     /// ```
+    ///     extern crate encoding;
+    ///     extern crate mailparse;
+    ///     use encoding::DecoderTrap;
     ///     use mailparse::parse_mail;
     ///     let p = parse_mail(concat!(
-    ///             "Subject: test\n",
+    ///             "Content-Type: text/plain; charset=utf-8\n",
     ///             "\n",
-    ///             "This is the body").as_bytes())
+    ///             "Bad \u{fffd}").as_bytes())
     ///         .unwrap();
-    ///     assert_eq!(p.get_body_raw().unwrap(), b"This is the body");
+    ///     assert_eq!(p.get_body_with_trap(DecoderTrap::Strict).unwrap(), "Bad \u{fffd}");
+    ///     let p = parse_mail(b"Content-Type: text/plain; charset=utf-8\n\nBad \xe2\xac").unwrap();
+    ///     assert!(p.get_body_with_trap(DecoderTrap::Strict).is_err());
+    ///     assert_eq!(p.get_body_with_trap(DecoderTrap::Replace).unwrap(), "Bad \u{fffd}");
     /// ```
-    pub fn get_body_raw(&self) -> Result<Vec<u8>, MailParseError> {
-        let transfer_coding = self
-            .headers
-            .get_first_value("Content-Transfer-Encoding")?
-            .map(|s| s.to_lowercase());
+    pub fn get_body_with_trap(&self, trap: encoding::DecoderTrap) -> Result<String, MailParseError> {
+        let decoded = self.get_body_decoded(quoted_printable::ParseMode::Robust)?;
+        let (encoding, decoded) = self.get_body_encoding(&decoded, None);
+        encoding.decode(decoded, trap).map_err(|e| e.into())
+    }
 
-        let decoded = match transfer_coding {
-            Some(ref enc) if enc == "base64" => {
-                let cleaned = self
+    /// Get the body of the message as a Rust string, as with `get_body`, but
+    /// using the given default charset if the Content-Type has no charset or
+    /// one that is not recognized. Strictly speaking a part without a charset
+    /// is "us-ascii", but sloppy messages often only declare the charset on
+    /// an enclosing multipart, or rely on the reader assuming one. If the
+    /// default charset is not recognized either, "us-ascii" is used.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/mixed; boundary=b; charset=utf-8\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "\n",
+    ///             "caf\u{e9}\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     let part = &p.subparts[0];
+    ///     assert_eq!(part.get_body().unwrap(), "caf\u{fffd}\u{fffd}\n");
+    ///     let charset = p.ctype.charset.as_ref().unwrap();
+    ///     assert_eq!(part.get_body_with_default_charset(charset).unwrap(), "caf\u{e9}\n");
+    /// ```
+    pub fn get_body_with_default_charset(&self, default: &str) -> Result<String, MailParseError> {
+        let decoded = self.get_body_decoded(quoted_printable::ParseMode::Robust)?;
+        let (encoding, decoded) = self.get_body_encoding(&decoded, Some(default));
+        encoding
+            .decode(decoded, encoding::DecoderTrap::Replace)
+            .map_err(|e| e.into())
+    }
+
+    /// Get the body of the message as a Rust string, as with `get_body`, along
+    /// with the name of the charset that was used to decode it. This is the
+    /// canonical name of the encoding that the Content-Type charset resolved
+    /// to (e.g. "windows-1252" for "iso-8859-1"), or "ascii" if the charset
+    /// was missing or not recognized. This is useful to track down why a
+    /// message was decoded incorrectly.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: text/plain; charset=latin-1\n",
+    ///             "\n",
+    ///             "This is the body").as_bytes())
+    ///         .unwrap();
+    ///     let (body, charset) = p.get_body_with_charset_info().unwrap();
+    ///     assert_eq!(body, "This is the body");
+    ///     assert_eq!(charset, "windows-1252");
+    ///     let p = parse_mail(b"Content-Type: text/plain; charset=bogus\n\nbody").unwrap();
+    ///     assert_eq!(p.get_body_with_charset_info().unwrap().1, "ascii");
+    /// ```
+    pub fn get_body_with_charset_info(&self) -> Result<(String, String), MailParseError> {
+        let decoded = self.get_body_decoded(quoted_printable::ParseMode::Robust)?;
+        let (encoding, decoded) = self.get_body_encoding(&decoded, None);
+        let body = encoding.decode(decoded, encoding::DecoderTrap::Replace)?;
+        Ok((body, encoding.name().to_string()))
+    }
+
+    /// Get the body of the message as a Rust string, as with `get_body`, and if
+    /// the Content-Type has the `format=flowed` parameter, undo the soft line
+    /// breaks as described in RFC 3676. Lines ending in a space are joined
+    /// with the next line (removing the space as well if the Content-Type has
+    /// `delsp=yes`), space-stuffing is removed, and quoted lines are only
+    /// joined with lines of the same quote depth. The lines in the result are
+    /// terminated by LF. Bodies that are not flowed are returned unchanged.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: text/plain; format=flowed\r\n",
+    ///             "\r\n",
+    ///             "This is a long \r\n",
+    ///             "paragraph.\r\n",
+    ///             "> Quoted \r\n",
+    ///             "> text\r\n").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_body_flowed().unwrap(), "This is a long paragraph.\n> Quoted text\n");
+    /// ```
+    pub fn get_body_flowed(&self) -> Result<String, MailParseError> {
+        let body = self.get_body()?;
+        let param_is = |name: &str, value: &str| {
+            self.ctype.params.get(name).is_some_and(|v| v.eq_ignore_ascii_case(value))
+        };
+        if !param_is("format", "flowed") {
+            return Ok(body);
+        }
+        Ok(flowed::unflow(&body, param_is("delsp", "yes")))
+    }
+
+    /// Get the body of the message as a Rust string, as with `get_body`, but
+    /// avoiding a copy where possible. If the body has an identity transfer
+    /// encoding and is already valid in a UTF-8 compatible charset (i.e. it is
+    /// UTF-8, or it is pure ASCII with an ASCII-compatible charset such as the
+    /// default "us-ascii"), the returned string borrows from the raw input.
+    ///
+    /// # Examples
+    /// ```
+    ///     use std::borrow::Cow;
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Subject: test\n",
+    ///             "\n",
+    ///             "This is the body").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_body_cow().unwrap(), Cow::Borrowed("This is the body"));
+    /// ```
+    pub fn get_body_cow(&self) -> Result<Cow<'a, str>, MailParseError> {
+        let decoded = self.get_body_decoded(quoted_printable::ParseMode::Robust)?;
+        if let Cow::Borrowed(bytes) = decoded {
+            let (charset_conv, bytes) = self.get_body_encoding(bytes, None);
+            let passthrough = match charset_conv.name() {
+                "utf-8" => true,
+                "ascii" | "windows-1252" => bytes.is_ascii(),
+                _ => false,
+            };
+            if passthrough {
+                if let Ok(s) = std::str::from_utf8(bytes) {
+                    return Ok(Cow::Borrowed(s));
+                }
+            }
+        }
+        let (charset_conv, decoded) = self.get_body_encoding(&decoded, None);
+        charset_conv
+            .decode(decoded, encoding::DecoderTrap::Replace)
+            .map(Cow::Owned)
+            .map_err(|e| e.into())
+    }
+
+    /// Find the encoding to use for converting the (transfer-decoded) body to
+    /// a Rust string, returning it along with the part of the body to convert.
+    /// A byte order mark at the start of the body determines the encoding by
+    /// itself, and is stripped off. Otherwise the encoding is based on the
+    /// charset in the Content-Type, or else the given default charset, or
+    /// else "us-ascii". A placeholder charset that explicitly says the charset
+    /// is unknown gives UTF-8 instead of "us-ascii", since that is the most
+    /// likely charset of such a body nowadays.
+    fn get_body_encoding<'b>(
+        &self,
+        body: &'b [u8],
+        default: Option<&str>,
+    ) -> (encoding::EncodingRef, &'b [u8]) {
+        let boms: [(&[u8], encoding::EncodingRef); 3] = [
+            (b"\xef\xbb\xbf", encoding::all::UTF_8),
+            (b"\xff\xfe", encoding::all::UTF_16LE),
+            (b"\xfe\xff", encoding::all::UTF_16BE),
+        ];
+        for &(bom, encoding) in &boms {
+            if let Some(rest) = body.strip_prefix(bom) {
+                return (encoding, rest);
+            }
+        }
+        let charset = self.ctype.charset.as_ref();
+        let encoding = charset
+            .and_then(|charset| charset_encoding(charset))
+            .or_else(|| default.and_then(charset_encoding))
+            .unwrap_or_else(|| match charset {
+                Some(charset) if is_unknown_charset(charset) => encoding::all::UTF_8,
+                _ => encoding::all::ASCII,
+            });
+        (encoding, body)
+    }
+
+    /// Get the body of the message as a Rust Vec<u8>. This function tries to
+    /// unapply the Content-Transfer-Encoding if there is one, but won't do
+    /// any charset decoding. The "7bit", "8bit" and "binary" encodings are
+    /// identity encodings and the body is returned as-is. Bodies with the
+    /// "x-uuencode" encoding are uudecoded. Any other encoding that is not
+    /// supported results in an `UnsupportedTransferEncoding` error. In a
+    /// quoted-printable body, every hard line break is a CRLF in the result,
+    /// even if the message uses bare LFs, as RFC 2045 defines line breaks in
+    /// that encoding to be CRLF; soft line breaks are removed.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Subject: test\n",
+    ///             "\n",
+    ///             "This is the body").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_body_raw().unwrap(), b"This is the body");
+    ///     let p = parse_mail(b"Content-Transfer-Encoding: quoted-printable\n\nOne=\ntwo\nthree\n").unwrap();
+    ///     assert_eq!(p.get_body_raw().unwrap(), b"Onetwo\r\nthree\r\n");
+    /// ```
+    pub fn get_body_raw(&self) -> Result<Vec<u8>, MailParseError> {
+        Ok(self.get_body_decoded(quoted_printable::ParseMode::Robust)?.into_owned())
+    }
+
+    /// Get the body of the message as a Rust Vec<u8>, as with `get_body_raw`,
+    /// but using the given mode to decode a quoted-printable body.
+    /// `get_body_raw` uses `ParseMode::Robust`, which tolerates the malformed
+    /// quoted-printable data that is common in incoming mail. Passing
+    /// `ParseMode::Strict` instead causes a `QuotedPrintableDecodeError` to be
+    /// returned for malformed data, which is useful to validate outgoing mail.
+    ///
+    /// # Examples
+    /// ```
+    ///     extern crate mailparse;
+    ///     extern crate quoted_printable;
+    ///     use mailparse::parse_mail;
+    ///     use quoted_printable::ParseMode;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Transfer-Encoding: quoted-printable\n",
+    ///             "\n",
+    ///             "caf=e9 =").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_body_raw_with_qp_mode(ParseMode::Robust).unwrap(), b"caf\xe9 ");
+    ///     assert!(p.get_body_raw_with_qp_mode(ParseMode::Strict).is_err());
+    /// ```
+    pub fn get_body_raw_with_qp_mode(
+        &self,
+        qp_mode: quoted_printable::ParseMode,
+    ) -> Result<Vec<u8>, MailParseError> {
+        Ok(self.get_body_decoded(qp_mode)?.into_owned())
+    }
+
+    /// Get the length in bytes of the body of the message after unapplying the
+    /// Content-Transfer-Encoding, i.e. the length of what `get_body_raw`
+    /// returns, without building the decoded body. This is useful to show the
+    /// size of (large) attachments. For base64 and quoted-printable bodies
+    /// the length is computed with a single scan over the encoded data; only
+    /// uuencoded and malformed base64 bodies are actually decoded. Errors are
+    /// returned in the same cases as by `get_body_raw`.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(b"Content-Transfer-Encoding: base64\n\naGVsbG8g\nd29ybGQ=\n").unwrap();
+    ///     assert_eq!(p.body_decoded_len().unwrap(), 11);
+    ///     let p = parse_mail(b"Content-Transfer-Encoding: quoted-printable\n\ncaf=C3=A9\n").unwrap();
+    ///     assert_eq!(p.body_decoded_len().unwrap(), "caf\u{e9}\r\n".len());
+    /// ```
+    pub fn body_decoded_len(&self) -> Result<usize, MailParseError> {
+        let len = match self.transfer_encoding()? {
+            TransferEncoding::Base64 => match base64_decoded_len(self.body) {
+                Some(len) => len,
+                None => self.get_body_decoded(quoted_printable::ParseMode::Robust)?.len(),
+            },
+            TransferEncoding::QuotedPrintable => quoted_printable_decoded_len(self.body),
+            TransferEncoding::Uuencode => {
+                self.get_body_decoded(quoted_printable::ParseMode::Robust)?.len()
+            }
+            TransferEncoding::SevenBit | TransferEncoding::EightBit | TransferEncoding::Binary => {
+                self.body.len()
+            }
+            TransferEncoding::Other(enc) => {
+                return Err(MailParseError::UnsupportedTransferEncoding(enc))
+            }
+        };
+        Ok(len)
+    }
+
+    /// Get the body of the message as a Rust string, as with `get_body`, but
+    /// using the given mode to decode a quoted-printable body, as described
+    /// for `get_body_raw_with_qp_mode`.
+    pub fn get_body_with_qp_mode(
+        &self,
+        qp_mode: quoted_printable::ParseMode,
+    ) -> Result<String, MailParseError> {
+        let decoded = self.get_body_decoded(qp_mode)?;
+        let (encoding, decoded) = self.get_body_encoding(&decoded, None);
+        encoding
+            .decode(decoded, encoding::DecoderTrap::Replace)
+            .map_err(|e| e.into())
+    }
+
+    /// Get the body of the message as a Rust Vec<u8>, as with `get_body_raw`,
+    /// but decoding base64 leniently. Any byte that is not in the base64
+    /// alphabet (including misplaced `=` padding characters) is skipped rather
+    /// than failing the whole decode, and an incomplete trailing character is
+    /// dropped. This is useful for recovering most of a corrupted attachment.
+    /// Bodies in other encodings are decoded exactly as by `get_body_raw`.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Transfer-Encoding: base64\n",
+    ///             "\n",
+    ///             "aGVs=bG8\x00gd29y*bGQ=").as_bytes())
+    ///         .unwrap();
+    ///     assert!(p.get_body_raw().is_err());
+    ///     assert_eq!(p.get_body_raw_lenient().unwrap(), b"hello world");
+    /// ```
+    pub fn get_body_raw_lenient(&self) -> Result<Vec<u8>, MailParseError> {
+        match self.transfer_encoding()? {
+            TransferEncoding::Base64 => {
+                let mut cleaned = self
                     .body
+                    .iter()
+                    .map(|c| to_standard_base64(*c))
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == b'+' || *c == b'/')
+                    .collect::<Vec<u8>>();
+                // A single leftover character doesn't encode a whole byte
+                if cleaned.len() % 4 == 1 {
+                    cleaned.pop();
+                }
+                Ok(base64::decode(&cleaned)?)
+            }
+            _ => self.get_body_raw(),
+        }
+    }
+
+    /// Get the body of the message as a Rust Vec<u8>, as with `get_body_raw`,
+    /// but bounded by the Content-Length header if there is one. Some message
+    /// stores add this header to give the length of the (still
+    /// transfer-encoded) body in bytes, and honoring it keeps trailing garbage
+    /// or a following message in a concatenated buffer out of the body. If the
+    /// body is shorter than the Content-Length, all of it is used. The header
+    /// is ignored if it is not a valid number, and for multipart messages,
+    /// whose bodies are bounded by the multipart boundary instead.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Length: 6\n",
+    ///             "\n",
+    ///             "Hello\n",
+    ///             "From someone else\n").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_body_bounded().unwrap(), b"Hello\n");
+    ///     assert_eq!(p.get_body_raw().unwrap(), b"Hello\nFrom someone else\n");
+    /// ```
+    pub fn get_body_bounded(&self) -> Result<Vec<u8>, MailParseError> {
+        let content_length = self
+            .headers
+            .get_first_value("Content-Length")?
+            .and_then(|v| v.trim().parse::<usize>().ok());
+        let body = match content_length {
+            Some(len) if !self.ctype.mimetype.starts_with("multipart/") => {
+                &self.body[..len.min(self.body.len())]
+            }
+            _ => self.body,
+        };
+        Ok(self.decode_body(body, quoted_printable::ParseMode::Robust)?.into_owned())
+    }
+
+    /// Unapply the Content-Transfer-Encoding of the body. For the identity
+    /// encodings this borrows the raw body rather than copying it.
+    fn get_body_decoded(
+        &self,
+        qp_mode: quoted_printable::ParseMode,
+    ) -> Result<Cow<'a, [u8]>, MailParseError> {
+        self.decode_body(self.body, qp_mode)
+    }
+
+    /// Unapply the Content-Transfer-Encoding of this message to the given
+    /// (part of the) body.
+    fn decode_body(
+        &self,
+        body: &'a [u8],
+        qp_mode: quoted_printable::ParseMode,
+    ) -> Result<Cow<'a, [u8]>, MailParseError> {
+        let decoded = match self.transfer_encoding()? {
+            TransferEncoding::Base64 => {
+                let cleaned = body
                     .iter()
                     .filter(|c| !c.is_ascii_whitespace())
-                    .cloned()
+                    .map(|c| to_standard_base64(*c))
                     .collect::<Vec<u8>>();
-                base64::decode(&cleaned)?
+                Cow::Owned(base64::decode(&cleaned)?)
+            }
+            TransferEncoding::QuotedPrintable => Cow::Owned(
+                quoted_printable::decode(body, qp_mode)?,
+            ),
+            TransferEncoding::Uuencode => Cow::Owned(uudecode::uudecode(body)?.1),
+            TransferEncoding::SevenBit | TransferEncoding::EightBit | TransferEncoding::Binary => {
+                Cow::Borrowed(body)
             }
-            Some(ref enc) if enc == "quoted-printable" => {
-                quoted_printable::decode(self.body, quoted_printable::ParseMode::Robust)?
+            TransferEncoding::Other(enc) => {
+                return Err(MailParseError::UnsupportedTransferEncoding(enc))
             }
-            _ => Vec::<u8>::from(self.body),
         };
         Ok(decoded)
     }
 
+    /// Get the Content-Transfer-Encoding of this message (or message
+    /// subpart). If there is no Content-Transfer-Encoding header, this is
+    /// `TransferEncoding::SevenBit`. Only the first header is used if there
+    /// are multiple. An error is returned if the value of the header can't be
+    /// decoded.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, TransferEncoding};
+    ///     let p = parse_mail(b"Content-Transfer-Encoding: Base64 (standard)\n\naGk=").unwrap();
+    ///     assert_eq!(p.transfer_encoding().unwrap(), TransferEncoding::Base64);
+    ///     let p = parse_mail(b"Content-Transfer-Encoding: x-bogus\n\nhi").unwrap();
+    ///     assert_eq!(p.transfer_encoding().unwrap(), TransferEncoding::Other("x-bogus".to_string()));
+    ///     let p = parse_mail(b"Subject: test\n\nhi").unwrap();
+    ///     assert_eq!(p.transfer_encoding().unwrap(), TransferEncoding::SevenBit);
+    /// ```
+    pub fn transfer_encoding(&self) -> Result<TransferEncoding, MailParseError> {
+        Ok(self
+            .headers
+            .get_first_value("Content-Transfer-Encoding")?
+            .map(|v| parse_transfer_encoding(&v))
+            .unwrap_or_default())
+    }
+
+    /// Returns the filename embedded in the `begin` line of a uuencoded body.
+    /// If the Content-Transfer-Encoding of this message (or message subpart)
+    /// is not "x-uuencode", or the begin line doesn't name a file, this
+    /// returns Ok(None).
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Transfer-Encoding: x-uuencode\n",
+    ///             "\n",
+    ///             "begin 644 cat.txt\n",
+    ///             "#0V%T\n",
+    ///             "`\n",
+    ///             "end\n").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_uuencoded_filename().unwrap(), Some("cat.txt".to_string()));
+    ///     assert_eq!(p.get_body().unwrap(), "Cat");
+    /// ```
+    pub fn get_uuencoded_filename(&self) -> Result<Option<String>, MailParseError> {
+        match self.transfer_encoding()? {
+            TransferEncoding::Uuencode => Ok(uudecode::uudecode(self.body)?.0),
+            _ => Ok(None),
+        }
+    }
+
     /// Returns a struct containing a parsed representation of the
     /// Content-Disposition header. The first header with this name
     /// is used, if there are multiple. See the `parse_content_disposition`
@@ -711,6 +2761,686 @@ impl<'a> ParsedMail<'a> {
             .unwrap_or_default();
         Ok(disposition)
     }
+
+    /// Classify this message part as body, inline media or attachment, using
+    /// the same kind of heuristic that mail clients use:
+    ///
+    /// * A part with an `attachment` disposition is an attachment.
+    /// * An image, audio or video part that has an explicit `inline`
+    ///   disposition or a Content-ID is inline.
+    /// * Any other part with a filename (in the Content-Disposition
+    ///   `filename` or Content-Type `name` parameter) is an attachment.
+    /// * Any other image, audio or video part is inline.
+    /// * Everything else is body.
+    ///
+    /// Headers that cannot be decoded are treated as if they were absent.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, DispositionKind};
+    ///     let p = parse_mail(b"Content-Type: text/plain\n\nHello").unwrap();
+    ///     assert_eq!(p.disposition_kind(), DispositionKind::Body);
+    ///     let p = parse_mail(b"Content-Type: image/png\nContent-ID: <logo>\n\nPNG").unwrap();
+    ///     assert_eq!(p.disposition_kind(), DispositionKind::Inline);
+    ///     let p = parse_mail(b"Content-Type: application/pdf; name=doc.pdf\n\nPDF").unwrap();
+    ///     assert_eq!(p.disposition_kind(), DispositionKind::Attachment);
+    /// ```
+    pub fn disposition_kind(&self) -> DispositionKind {
+        let header_value = |key: &str| self.headers.get_first_value(key).ok().and_then(|v| v);
+        let disposition = header_value("Content-Disposition").map(|v| parse_content_disposition(&v));
+        let is_media = matches!(
+            self.ctype.kind(),
+            MimeKind::Image | MimeKind::Audio | MimeKind::Video
+        );
+        let has_filename = self.ctype.params.contains_key("name") ||
+            disposition.as_ref().is_some_and(|d| d.params.contains_key("filename"));
+        match disposition.map(|d| d.disposition) {
+            Some(DispositionType::Attachment) => DispositionKind::Attachment,
+            Some(DispositionType::Inline) if is_media => DispositionKind::Inline,
+            _ if is_media && header_value("Content-ID").is_some() => DispositionKind::Inline,
+            _ if has_filename => DispositionKind::Attachment,
+            _ if is_media => DispositionKind::Inline,
+            _ => DispositionKind::Body,
+        }
+    }
+
+    /// Find the part with the given Content-ID, searching this part and all
+    /// of its subparts (depth-first). This is useful for resolving `cid:`
+    /// URLs in multipart/related messages. The angle brackets around the
+    /// id are optional, both in the header and in the `cid` argument, but
+    /// the id itself is compared case-sensitively.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/related; boundary=\"b\"\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/html\n",
+    ///             "\n",
+    ///             "<img src=\"cid:logo@example.com\">\n",
+    ///             "--b\n",
+    ///             "Content-Type: image/png\n",
+    ///             "Content-ID: <logo@example.com>\n",
+    ///             "\n",
+    ///             "PNG\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     let image = p.get_part_by_content_id("logo@example.com").unwrap();
+    ///     assert_eq!(image.ctype.mimetype, "image/png");
+    ///     assert!(p.get_part_by_content_id("<LOGO@example.com>").is_none());
+    /// ```
+    pub fn get_part_by_content_id(&self, cid: &str) -> Option<&ParsedMail<'a>> {
+        let cid = strip_angle_brackets(cid);
+        let matches = self
+            .headers
+            .get_first_value("Content-ID")
+            .ok()
+            .and_then(|v| v)
+            .is_some_and(|v| strip_angle_brackets(&v) == cid);
+        if matches {
+            return Some(self);
+        }
+        self.subparts
+            .iter()
+            .find_map(|part| part.get_part_by_content_id(cid))
+    }
+
+    /// Get the part with the given IMAP part number (as used in the BODY[...]
+    /// and BODYSTRUCTURE items of RFC 3501), given as a list of the numbers
+    /// in the dotted path, e.g. `&[2, 1]` for part `2.1`. The empty path is
+    /// the message itself. The parts of a multipart are numbered from 1, and
+    /// a non-multipart message has a single part 1, which is the message
+    /// itself. The parts of a message encapsulated in a message/rfc822 part
+    /// are numbered beneath that part as if it were the top level, so the
+    /// encapsulated message itself (which is the single subpart of the
+    /// message/rfc822 part) has no number unless it is not a multipart.
+    /// Returns None if there is no such part.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/mixed; boundary=\"b\"\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/plain\n",
+    ///             "\n",
+    ///             "See the forwarded message.\n",
+    ///             "--b\n",
+    ///             "Content-Type: message/rfc822\n",
+    ///             "\n",
+    ///             "Content-Type: multipart/alternative; boundary=\"c\"\n",
+    ///             "\n",
+    ///             "--c\n",
+    ///             "Content-Type: text/plain\n",
+    ///             "\n",
+    ///             "Hello\n",
+    ///             "--c\n",
+    ///             "Content-Type: text/html\n",
+    ///             "\n",
+    ///             "<p>Hello</p>\n",
+    ///             "--c--\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.part_by_path(&[]).unwrap().ctype.mimetype, "multipart/mixed");
+    ///     assert_eq!(p.part_by_path(&[2]).unwrap().ctype.mimetype, "message/rfc822");
+    ///     assert_eq!(p.part_by_path(&[2, 2]).unwrap().ctype.mimetype, "text/html");
+    ///     assert!(p.part_by_path(&[3]).is_none());
+    ///     assert!(p.part_by_path(&[0]).is_none());
+    /// ```
+    pub fn part_by_path(&self, path: &[usize]) -> Option<&ParsedMail<'a>> {
+        let mut part = self;
+        let mut numbered = self.imap_body_parts();
+        for n in path {
+            part = numbered.get(n.checked_sub(1)?)?;
+            numbered = part.imap_child_parts();
+        }
+        Some(part)
+    }
+
+    /// Iterate over the parts of the message along with their IMAP part
+    /// numbers, as described for `part_by_path`. The message itself comes
+    /// first, with the empty path, followed by the numbered parts in
+    /// depth-first order. Note that for a non-multipart message, the message
+    /// is yielded twice: with the empty path, and as part 1.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/mixed; boundary=\"b\"\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/plain\n",
+    ///             "\n",
+    ///             "Hello\n",
+    ///             "--b\n",
+    ///             "Content-Type: image/png\n",
+    ///             "\n",
+    ///             "PNG\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     let paths: Vec<(Vec<usize>, &str)> = p
+    ///         .parts_with_paths()
+    ///         .map(|(path, part)| (path, &part.ctype.mimetype[..]))
+    ///         .collect();
+    ///     assert_eq!(paths, vec![
+    ///         (vec![], "multipart/mixed"),
+    ///         (vec![1], "text/plain"),
+    ///         (vec![2], "image/png"),
+    ///     ]);
+    /// ```
+    pub fn parts_with_paths(&self) -> impl Iterator<Item = (Vec<usize>, &ParsedMail<'a>)> {
+        let mut parts = vec![(Vec::new(), self)];
+        collect_parts_with_paths(self.imap_body_parts(), &mut Vec::new(), &mut parts);
+        parts.into_iter()
+    }
+
+    /// The parts that are numbered 1, 2, ... when this is the top level of a
+    /// message, as far as IMAP part numbers are concerned.
+    fn imap_body_parts(&self) -> &[ParsedMail<'a>] {
+        if self.ctype.mimetype.starts_with("multipart/") {
+            &self.subparts
+        } else {
+            std::slice::from_ref(self)
+        }
+    }
+
+    /// The parts that are numbered beneath this part, as far as IMAP part
+    /// numbers are concerned.
+    fn imap_child_parts(&self) -> &[ParsedMail<'a>] {
+        if self.ctype.mimetype.starts_with("multipart/") {
+            &self.subparts
+        } else if matches!(&self.ctype.mimetype[..], "message/rfc822" | "message/global") {
+            self.subparts.first().map_or(&[], ParsedMail::imap_body_parts)
+        } else {
+            &[]
+        }
+    }
+
+    /// Collect this part and all of its descendants whose mimetype matches
+    /// the given one, in depth-first order. The mimetype may either be a
+    /// full `type/subtype`, or a `type/*` wildcard which matches any subtype.
+    /// The comparison is case-insensitive.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/mixed; boundary=\"b\"\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/html\n",
+    ///             "\n",
+    ///             "<p>Hello</p>\n",
+    ///             "--b\n",
+    ///             "Content-Type: image/png\n",
+    ///             "\n",
+    ///             "PNG\n",
+    ///             "--b\n",
+    ///             "Content-Type: image/jpeg\n",
+    ///             "\n",
+    ///             "JPEG\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.parts_of_type("image/*").len(), 2);
+    ///     assert_eq!(p.parts_of_type("Text/HTML")[0].get_body().unwrap(), "<p>Hello</p>\n");
+    ///     assert!(p.parts_of_type("text/plain").is_empty());
+    /// ```
+    pub fn parts_of_type(&self, mimetype: &str) -> Vec<&ParsedMail<'a>> {
+        let mut parts = Vec::new();
+        self.collect_parts_of_type(&mimetype.trim().to_lowercase(), &mut parts);
+        parts
+    }
+
+    /// Get the best plain text body of the message, for e.g. a preview. The
+    /// first text/plain part that is not an attachment is used, searching
+    /// this part and all of its subparts depth-first. If there is none, the
+    /// first text/html part that is not an attachment is used instead, and
+    /// its body is returned as HTML; `html_to_text` can be used to turn that
+    /// into a plain text preview. Returns Ok(None) if there is neither.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/alternative; boundary=\"b\"\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/html\n",
+    ///             "\n",
+    ///             "<p>Hello</p>\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/plain\n",
+    ///             "\n",
+    ///             "Hello\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_text_body().unwrap(), Some("Hello\n".to_string()));
+    ///     let p = parse_mail(b"Content-Type: image/png\n\nPNG").unwrap();
+    ///     assert_eq!(p.get_text_body().unwrap(), None);
+    /// ```
+    pub fn get_text_body(&self) -> Result<Option<String>, MailParseError> {
+        self.get_preferred_body(&["text/plain", "text/html"])
+    }
+
+    /// Get the body of the message in the first of the given mimetypes that
+    /// it is available in, e.g. to pick between the parts of a
+    /// multipart/alternative message. For each mimetype in order, this and
+    /// all of its subparts are searched depth-first for a part of that
+    /// mimetype that is not an attachment, and the decoded body of the first
+    /// one found is returned. The mimetypes are matched as with
+    /// `parts_of_type`, so e.g. "text/*" matches any text part. Returns
+    /// Ok(None) if no part matches any of the mimetypes. `get_text_body` is
+    /// this with a preference for plain text over HTML.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/alternative; boundary=\"b\"\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/plain\n",
+    ///             "\n",
+    ///             "Hello\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/html\n",
+    ///             "\n",
+    ///             "<p>Hello</p>\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.get_preferred_body(&["text/html", "text/plain"]).unwrap(),
+    ///                Some("<p>Hello</p>\n".to_string()));
+    ///     assert_eq!(p.get_preferred_body(&["text/enriched", "text/*"]).unwrap(),
+    ///                Some("Hello\n".to_string()));
+    ///     assert_eq!(p.get_preferred_body(&["image/*"]).unwrap(), None);
+    /// ```
+    pub fn get_preferred_body(&self, order: &[&str]) -> Result<Option<String>, MailParseError> {
+        for mimetype in order {
+            let part = self
+                .parts_of_type(mimetype)
+                .into_iter()
+                .find(|part| part.disposition_kind() != DispositionKind::Attachment);
+            if let Some(part) = part {
+                return part.get_body().map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Sniff whether the body of this message (or message subpart) looks like
+    /// an HTML document, for deciding how to render a message that has no
+    /// (or only a generic) Content-Type. The check is conservative: after
+    /// unapplying the Content-Transfer-Encoding and skipping any leading
+    /// whitespace and byte order mark, the body must start with an HTML
+    /// doctype or with an `html`, `head` or `body` tag. A plain text body
+    /// that merely contains markup somewhere doesn't count. This returns false
+    /// if the body cannot be decoded. Note that the Content-Type is not
+    /// consulted.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(b"Subject: test\n\n  <!DOCTYPE html>\n<html><p>Hi</p></html>").unwrap();
+    ///     assert!(p.looks_like_html());
+    ///     let p = parse_mail(b"Subject: test\n\nUse <html> to start a page").unwrap();
+    ///     assert!(!p.looks_like_html());
+    /// ```
+    pub fn looks_like_html(&self) -> bool {
+        let body = match self.get_body_decoded(quoted_printable::ParseMode::Robust) {
+            Ok(body) => body,
+            Err(_) => return false,
+        };
+        let body = body.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&body);
+        let start = body.iter().position(|c| !c.is_ascii_whitespace()).unwrap_or(body.len());
+        let body = &body[start..];
+        ["<!doctype html", "<html", "<head", "<body"].iter().any(|tag| {
+            body.len() > tag.len() &&
+                body[..tag.len()].eq_ignore_ascii_case(tag.as_bytes()) &&
+                (body[tag.len()].is_ascii_whitespace() || body[tag.len()] == b'>')
+        })
+    }
+
+    /// Get all the parts of the message that are attachments, as classified
+    /// by `disposition_kind`, searching this part and all of its subparts
+    /// depth-first. Body parts and inline media (such as images referenced by
+    /// an HTML body through their Content-ID) are skipped. The subparts of an
+    /// attachment (e.g. of an attached message/rfc822) are not searched, so
+    /// that an attached message counts as a single attachment.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/mixed; boundary=\"b\"\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/plain\n",
+    ///             "\n",
+    ///             "See attached.\n",
+    ///             "--b\n",
+    ///             "Content-Type: application/pdf\n",
+    ///             "Content-Disposition: attachment; filename=\"report.pdf\"\n",
+    ///             "\n",
+    ///             "PDF\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     let attachments = p.attachments();
+    ///     assert_eq!(attachments.len(), 1);
+    ///     assert_eq!(attachments[0].ctype.mimetype, "application/pdf");
+    /// ```
+    pub fn attachments(&self) -> Vec<&ParsedMail<'a>> {
+        let mut attachments = Vec::new();
+        self.collect_attachments(&mut attachments);
+        attachments
+    }
+
+    /// Get the number of attachments in the message, i.e. the number of parts
+    /// returned by `attachments`.
+    pub fn attachment_count(&self) -> usize {
+        self.attachments().len()
+    }
+
+    /// Get all the mailboxes in the From, To, Cc, Bcc, Reply-To and Sender
+    /// headers of this message, in that order, without duplicates. Mailboxes
+    /// are considered duplicates if their addresses are equal ignoring ASCII
+    /// case; only the first one is kept, with the first display name that was
+    /// seen for the address. The mailboxes of groups are included as if they
+    /// were listed individually, so every entry is a `MailAddr::Single`.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::{parse_mail, MailAddr, SingleInfo};
+    ///     let p = parse_mail(concat!(
+    ///             "From: jane@example.com\n",
+    ///             "To: Team: John Doe <john@example.com>, Jane <JANE@example.com>;\n",
+    ///             "Cc: John <john@example.com>\n",
+    ///             "\n",
+    ///             "Body").as_bytes())
+    ///         .unwrap();
+    ///     let single = |name: Option<&str>, addr: &str| MailAddr::Single(SingleInfo {
+    ///         display_name: name.map(String::from),
+    ///         addr: addr.to_string(),
+    ///     });
+    ///     assert_eq!(p.all_addresses().unwrap(), vec![
+    ///         single(Some("Jane"), "jane@example.com"),
+    ///         single(Some("John Doe"), "john@example.com"),
+    ///     ]);
+    /// ```
+    pub fn all_addresses(&self) -> Result<Vec<MailAddr>, MailParseError> {
+        let mut mailboxes: Vec<SingleInfo> = Vec::new();
+        for key in &["From", "To", "Cc", "Bcc", "Reply-To", "Sender"] {
+            for addr in self.headers.get_addresses(key)? {
+                let infos = match addr {
+                    MailAddr::Single(info) => vec![info],
+                    MailAddr::Group(group) => group.addrs,
+                };
+                for info in infos {
+                    match mailboxes.iter_mut().find(|m| m.addr.eq_ignore_ascii_case(&info.addr)) {
+                        Some(existing) => {
+                            if existing.display_name.is_none() {
+                                existing.display_name = info.display_name;
+                            }
+                        }
+                        None => mailboxes.push(info),
+                    }
+                }
+            }
+        }
+        Ok(mailboxes.into_iter().map(MailAddr::Single).collect())
+    }
+
+    /// If this is a `multipart/signed` part (RFC 1847) with exactly two
+    /// subparts, get the first one, which is the content that was signed.
+    /// The signature itself is returned by `signature_part`. To verify the
+    /// signature, the `protocol` and `micalg` parameters are available in
+    /// `ctype.params`, and `get_body_encoded` on this part returns the raw
+    /// bytes including the headers of the signed content. Returns None for
+    /// any other part, or if the number of subparts is wrong.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/signed; boundary=b;\n",
+    ///             "    protocol=\"application/pgp-signature\"; micalg=pgp-sha256\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/plain\n",
+    ///             "\n",
+    ///             "Signed text\n",
+    ///             "--b\n",
+    ///             "Content-Type: application/pgp-signature\n",
+    ///             "\n",
+    ///             "SIGNATURE\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     assert_eq!(p.ctype.params["protocol"], "application/pgp-signature");
+    ///     assert_eq!(p.ctype.params["micalg"], "pgp-sha256");
+    ///     assert_eq!(p.signed_content().unwrap().get_body().unwrap(), "Signed text\n");
+    ///     assert_eq!(p.signature_part().unwrap().ctype.mimetype, "application/pgp-signature");
+    ///     assert!(p.subparts[0].signed_content().is_none());
+    /// ```
+    pub fn signed_content(&self) -> Option<&ParsedMail<'a>> {
+        self.signed_parts().map(|(content, _)| content)
+    }
+
+    /// If this is a `multipart/signed` part with exactly two subparts, get
+    /// the second one, which holds the signature. See `signed_content`.
+    pub fn signature_part(&self) -> Option<&ParsedMail<'a>> {
+        self.signed_parts().map(|(_, signature)| signature)
+    }
+
+    fn signed_parts(&self) -> Option<(&ParsedMail<'a>, &ParsedMail<'a>)> {
+        match self.subparts[..] {
+            [ref content, ref signature] if self.ctype.mimetype == "multipart/signed" => {
+                Some((content, signature))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the images that are displayed inline (as classified by
+    /// `disposition_kind`) and have a Content-ID, searching this part and all
+    /// of its subparts depth-first. For each image this returns its
+    /// Content-ID (without the angle brackets), its mimetype, and its body
+    /// as decoded by `get_body_raw`. This is what is needed to resolve the
+    /// `cid:` URLs in an HTML body, e.g. to turn them into data URIs.
+    ///
+    /// # Examples
+    /// ```
+    ///     use mailparse::parse_mail;
+    ///     let p = parse_mail(concat!(
+    ///             "Content-Type: multipart/related; boundary=\"b\"\n",
+    ///             "\n",
+    ///             "--b\n",
+    ///             "Content-Type: text/html\n",
+    ///             "\n",
+    ///             "<img src=\"cid:logo@example.com\">\n",
+    ///             "--b\n",
+    ///             "Content-Type: image/png\n",
+    ///             "Content-ID: <logo@example.com>\n",
+    ///             "Content-Transfer-Encoding: base64\n",
+    ///             "\n",
+    ///             "iVBORw==\n",
+    ///             "--b--\n").as_bytes())
+    ///         .unwrap();
+    ///     let images = p.inline_images().unwrap();
+    ///     assert_eq!(images, vec![(
+    ///         "logo@example.com".to_string(),
+    ///         "image/png".to_string(),
+    ///         b"\x89PNG".to_vec(),
+    ///     )]);
+    /// ```
+    pub fn inline_images(&self) -> Result<Vec<(String, String, Vec<u8>)>, MailParseError> {
+        let mut images = Vec::new();
+        for part in self.parts_of_type("image/*") {
+            if part.disposition_kind() != DispositionKind::Inline {
+                continue;
+            }
+            if let Some(cid) = part.headers.get_first_value("Content-ID")? {
+                images.push((
+                    strip_angle_brackets(&cid).to_string(),
+                    part.ctype.mimetype.clone(),
+                    part.get_body_raw()?,
+                ));
+            }
+        }
+        Ok(images)
+    }
+
+    fn collect_attachments<'s>(&'s self, attachments: &mut Vec<&'s ParsedMail<'a>>) {
+        if self.disposition_kind() == DispositionKind::Attachment {
+            attachments.push(self);
+            return;
+        }
+        for part in &self.subparts {
+            part.collect_attachments(attachments);
+        }
+    }
+
+    fn collect_parts_of_type<'s>(&'s self, mimetype: &str, parts: &mut Vec<&'s ParsedMail<'a>>) {
+        let matches = match mimetype.strip_suffix("/*") {
+            Some(toplevel) => self
+                .ctype
+                .mimetype
+                .split('/')
+                .next()
+                .is_some_and(|t| t.eq_ignore_ascii_case(toplevel)),
+            None => self.ctype.mimetype.eq_ignore_ascii_case(mimetype),
+        };
+        if matches {
+            parts.push(self);
+        }
+        for part in &self.subparts {
+            part.collect_parts_of_type(mimetype, parts);
+        }
+    }
+}
+
+/// Check if the charset is one of the placeholders that some senders declare
+/// when they don't know the actual charset of the data.
+fn is_unknown_charset(charset: &str) -> bool {
+    let charset = charset.trim();
+    charset.is_empty() ||
+        charset.eq_ignore_ascii_case("x-unknown") ||
+        charset.eq_ignore_ascii_case("unknown")
+}
+
+/// Add the given numbered parts and their descendants to `parts`, along with
+/// their IMAP part numbers. `path` is the part number of their parent.
+fn collect_parts_with_paths<'s, 'a>(
+    numbered: &'s [ParsedMail<'a>],
+    path: &mut Vec<usize>,
+    parts: &mut Vec<(Vec<usize>, &'s ParsedMail<'a>)>,
+) {
+    for (n, part) in (1..).zip(numbered) {
+        path.push(n);
+        parts.push((path.clone(), part));
+        collect_parts_with_paths(part.imap_child_parts(), path, parts);
+        path.pop();
+    }
+}
+
+/// Get the length of the lines at the start of the data that only consist of
+/// spaces and tabs. Such lines can never be part of a valid header, but some
+/// senders put them before the headers of a message or part.
+fn whitespace_lines_len(raw_data: &[u8]) -> usize {
+    let mut ix = 0;
+    loop {
+        let rest = &raw_data[ix..];
+        let ws = rest.iter().take_while(|c| **c == b' ' || **c == b'\t').count();
+        if ws == 0 {
+            return ix;
+        }
+        match &rest[ws..] {
+            [] => return raw_data.len(),
+            [b'\r', b'\n', ..] => ix += ws + 2,
+            [b'\r', ..] | [b'\n', ..] => ix += ws + 1,
+            _ => return ix,
+        }
+    }
+}
+
+/// Trim whitespace and a surrounding pair of angle brackets from an id.
+fn strip_angle_brackets(id: &str) -> &str {
+    let id = id.trim();
+    if id.starts_with('<') && id.ends_with('>') && id.len() >= 2 {
+        id[1..id.len() - 1].trim()
+    } else {
+        id
+    }
+}
+
+/// An owned counterpart to `ParsedMail`, created with `ParsedMail::to_owned`.
+/// This holds copies of all the raw header and body bytes, so it is not tied
+/// to the lifetime of the input that was parsed. Use `as_parsed_mail` to get
+/// at the data using the regular `ParsedMail` API.
+#[derive(Debug)]
+pub struct ParsedMailOwned {
+    headers: Vec<(Vec<u8>, Vec<u8>)>,
+    ctype: ParsedContentType,
+    body: Vec<u8>,
+    subparts: Vec<ParsedMailOwned>,
+    truncated: bool,
+}
+
+impl ParsedMailOwned {
+    /// Get a `ParsedMail` view of this message, borrowing from the data owned
+    /// by this struct.
+    pub fn as_parsed_mail(&self) -> ParsedMail<'_> {
+        ParsedMail {
+            headers: self
+                .headers
+                .iter()
+                .map(|(key, value)| MailHeader { key, value })
+                .collect(),
+            ctype: self.ctype.clone(),
+            body: &self.body,
+            subparts: self.subparts.iter().map(ParsedMailOwned::as_parsed_mail).collect(),
+            truncated: self.truncated,
+        }
+    }
+}
+
+/// Check if the error is one that is only reported because of a limit or
+/// strict check enabled in `ParseOptions`.
+fn is_option_error(kind: &ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::NestingTooDeep |
+            ErrorKind::HeaderTooLarge |
+            ErrorKind::UnterminatedHeaders |
+            ErrorKind::EmptyKey |
+            ErrorKind::InvalidKeyCharacter
+    )
+}
+
+/// Find the next occurrence of the multipart boundary delimiter in the raw
+/// data, starting the search at `ix_start`. RFC 2046 requires the delimiter
+/// to appear at the beginning of a line, so any occurrence in the middle of a
+/// line (e.g. inside base64 data) is skipped over. The delimiter must also
+/// be followed by the end of the line (possibly after whitespace padding),
+/// the `--` of a close delimiter, or the end of the data. Otherwise a nested
+/// multipart whose boundary has the outer boundary as a prefix (e.g. `abc`
+/// and `abc1`) would have its delimiters mistaken for the outer ones.
+fn find_boundary(raw_data: &[u8], ix_start: usize, boundary: &[u8]) -> Option<usize> {
+    let mut ix_search = ix_start;
+    loop {
+        let ix = find_from_u8(raw_data, ix_search, boundary)?;
+        let at_line_start = ix == 0 || raw_data.get(ix - 1) == Some(&b'\n');
+        let rest = &raw_data[ix + boundary.len()..];
+        let at_delimiter_end = rest.is_empty() ||
+            rest.starts_with(b"--") ||
+            matches!(rest[0], b'\r' | b'\n' | b' ' | b'\t');
+        if at_line_start && at_delimiter_end {
+            return Some(ix);
+        }
+        ix_search = ix + 1;
+    }
 }
 
 /// The main mail-parsing entry point.
@@ -718,6 +3448,12 @@ impl<'a> ParsedMail<'a> {
 /// structured version of it, which allows easily accessing the header and body
 /// information as needed.
 ///
+/// Any lines at the start of the input that only contain whitespace are
+/// skipped. An empty input, or one that starts with a blank line, is parsed
+/// as a message without headers whose body is whatever follows the blank
+/// line. Input that starts with whitespace followed by other text, or with
+/// text that is not a header, results in an error.
+///
 /// # Examples
 /// ```
 ///     use mailparse::*;
@@ -744,52 +3480,193 @@ impl<'a> ParsedMail<'a> {
 ///         Some("This is a test email".to_string()));
 ///     assert_eq!(parsed.subparts.len(), 2);
 ///     assert_eq!(parsed.subparts[0].get_body().unwrap(),
-///         "This is the plaintext version, in utf-8. Proof by Euro: \u{20AC}");
+///         "This is the plaintext version, in utf-8. Proof by Euro: \u{20AC}\r\n");
 ///     assert_eq!(parsed.subparts[1].headers[1].get_value().unwrap(), "base64");
 ///     assert_eq!(parsed.subparts[1].ctype.mimetype, "text/html");
 ///     assert!(parsed.subparts[1].get_body().unwrap().starts_with("<html>"));
 ///     assert_eq!(dateparse(parsed.headers.get_first_value("Date").unwrap().unwrap().as_str()).unwrap(), 1475417182);
 /// ```
-pub fn parse_mail(raw_data: &[u8]) -> Result<ParsedMail, MailParseError> {
-    let (headers, ix_body) = parse_headers(raw_data)?;
-    let ctype = headers
-        .get_first_value("Content-Type")?
-        .map(|s| parse_content_type(&s))
-        .unwrap_or_default();
+pub fn parse_mail(raw_data: &[u8]) -> Result<ParsedMail<'_>, MailParseError> {
+    parse_mail_with_options(raw_data, &ParseOptions::default())
+}
+
+/// Parse an email message, as with `parse_mail`, but using the given options.
+/// The options are applied to the header block of every part, and
+/// `max_depth` limits how deeply nested parts are parsed.
+///
+/// # Examples
+/// ```
+///     use mailparse::{parse_mail_with_options, ErrorKind, MailParseError, ParseOptions};
+///     let raw = concat!(
+///         "Content-Type: multipart/mixed; boundary=outer\n",
+///         "\n",
+///         "--outer\n",
+///         "Content-Type: multipart/mixed; boundary=inner\n",
+///         "\n",
+///         "--inner\n",
+///         "\n",
+///         "Deep\n",
+///         "--inner--\n",
+///         "--outer--\n");
+///     let options = ParseOptions { max_depth: 2, ..Default::default() };
+///     let mail = parse_mail_with_options(raw.as_bytes(), &options).unwrap();
+///     assert_eq!(mail.subparts[0].subparts[0].get_body().unwrap(), "Deep\n");
+///     let options = ParseOptions { max_depth: 1, ..Default::default() };
+///     match parse_mail_with_options(raw.as_bytes(), &options) {
+///         Err(MailParseError::Generic(ErrorKind::NestingTooDeep, 110)) => (),
+///         _ => panic!("Nesting limit was not enforced"),
+///     }
+/// ```
+pub fn parse_mail_with_options<'a>(
+    raw_data: &'a [u8],
+    options: &ParseOptions,
+) -> Result<ParsedMail<'a>, MailParseError> {
+    parse_mail_at_depth(raw_data, options, 0, false)
+}
+
+fn parse_mail_at_depth<'a>(
+    raw_data: &'a [u8],
+    options: &ParseOptions,
+    depth: usize,
+    in_digest: bool,
+) -> Result<ParsedMail<'a>, MailParseError> {
+    if depth > options.max_depth {
+        return Err(MailParseError::Generic(ErrorKind::NestingTooDeep, 0));
+    }
+    let ix_start = whitespace_lines_len(raw_data);
+    let (headers, ix_body) = parse_headers_with_options(&raw_data[ix_start..], options)
+        .map_err(|e| e.offset_by(ix_start))?;
+    let ix_body = ix_start + ix_body;
+    let ctype = if in_digest && headers.get_first_header("Content-Type").is_none() {
+        ParsedContentType { mimetype: "message/rfc822".to_string(), ..Default::default() }
+    } else {
+        ParsedContentType::from_headers(&headers)?
+    };
 
     let mut result = ParsedMail {
         headers,
         ctype,
         body: &raw_data[ix_body..],
         subparts: Vec::<ParsedMail>::new(),
+        truncated: false,
     };
     if result.ctype.mimetype.starts_with("multipart/") &&
-        result.ctype.params.get("boundary").is_some() && raw_data.len() > ix_body
+        result.ctype.params.contains_key("boundary") && raw_data.len() > ix_body
     {
         let boundary = String::from("--") + &result.ctype.params["boundary"];
-        if let Some(ix_body_end) = find_from_u8(raw_data, ix_body, boundary.as_bytes()) {
+        let is_digest = result.ctype.mimetype == "multipart/digest";
+        if let Some(ix_body_end) = find_boundary(raw_data, ix_body, boundary.as_bytes()) {
+            let mut closed = false;
             result.body = &raw_data[ix_body..ix_body_end];
             let mut ix_boundary_end = ix_body_end + boundary.len();
             while let Some(ix_part_start) =
                 find_from_u8(raw_data, ix_boundary_end, b"\n").map(|v| v + 1)
             {
                 // if there is no terminating boundary, assume the part end is the end of the email
-                let ix_part_end = find_from_u8(raw_data, ix_part_start, boundary.as_bytes())
-                    .unwrap_or_else(|| raw_data.len());
+                let ix_part_end = find_boundary(raw_data, ix_part_start, boundary.as_bytes())
+                    .unwrap_or(raw_data.len());
 
-                result.subparts.push(parse_mail(&raw_data[ix_part_start..ix_part_end])?);
+                result.subparts.push(
+                    parse_mail_at_depth(
+                        &raw_data[ix_part_start..ix_part_end],
+                        options,
+                        depth + 1,
+                        is_digest,
+                    )
+                    .map_err(|e| e.offset_by(ix_part_start))?,
+                );
                 ix_boundary_end = ix_part_end + boundary.len();
-                if ix_boundary_end + 2 > raw_data.len() ||
-                    (raw_data[ix_boundary_end] == b'-' && raw_data[ix_boundary_end + 1] == b'-')
-                {
-                    break;
+                // Skip any whitespace padding after the boundary delimiter
+                while raw_data.get(ix_boundary_end).is_some_and(|c| *c == b' ' || *c == b'\t') {
+                    ix_boundary_end += 1;
+                }
+                match raw_data.get(ix_boundary_end..ix_boundary_end.saturating_add(2)) {
+                    Some(b"--") => {
+                        closed = true;
+                        break;
+                    }
+                    None => break,
+                    Some(_) => (),
+                }
+            }
+            result.truncated = !closed;
+        }
+    } else if (result.ctype.mimetype == "message/rfc822" ||
+                   result.ctype.mimetype == "message/global") && !result.body.is_empty()
+    {
+        // The body is itself a complete message. It can only be parsed in
+        // place if it has not been transfer-encoded.
+        if matches!(result.transfer_encoding(), Ok(ref enc) if enc.is_identity()) {
+            match parse_mail_at_depth(result.body, options, depth + 1, false) {
+                Ok(message) => result.subparts.push(message),
+                // The limits and strict checks set in the options still
+                // apply, as the caller asked for those explicitly
+                Err(MailParseError::Generic(kind, ix)) if is_option_error(&kind) => {
+                    return Err(MailParseError::Generic(kind, ix).offset_by(ix_body));
                 }
+                // Otherwise a body that isn't a valid message is just left
+                // as it is, rather than making the whole message unreadable
+                Err(_) => (),
             }
         }
     }
     Ok(result)
 }
 
+/// The envelope information from the `From ` separator line that precedes
+/// each message in an mbox file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MboxEnvelope {
+    /// The envelope sender, i.e. the first word after `From `.
+    pub sender: String,
+    /// The rest of the separator line, which is normally the date the message
+    /// was delivered in asctime format, e.g. "Sat Jan  3 01:05:34 1996".
+    pub date: String,
+}
+
+/// Like `parse_mail`, but for a message taken out of an mbox file. If the data
+/// starts with a `From ` envelope line, that line is skipped before parsing
+/// the message and returned as an `MboxEnvelope`. If there is no such line,
+/// the message is parsed as-is and the envelope is None. Offsets in any errors
+/// are relative to the start of `raw_data`, including the envelope line.
+///
+/// # Examples
+/// ```
+///     use mailparse::*;
+///     let (envelope, parsed) = parse_mail_mbox(concat!(
+///             "From alice@example.com Sat Jan  3 01:05:34 1996\n",
+///             "Subject: Hello\n",
+///             "\n",
+///             "Body").as_bytes())
+///         .unwrap();
+///     let envelope = envelope.unwrap();
+///     assert_eq!(envelope.sender, "alice@example.com");
+///     assert_eq!(envelope.date, "Sat Jan  3 01:05:34 1996");
+///     assert_eq!(parsed.headers.get_first_value("Subject").unwrap(), Some("Hello".to_string()));
+///     assert_eq!(parsed.get_body().unwrap(), "Body");
+/// ```
+pub fn parse_mail_mbox(
+    raw_data: &[u8],
+) -> Result<(Option<MboxEnvelope>, ParsedMail<'_>), MailParseError> {
+    if !raw_data.starts_with(b"From ") {
+        return Ok((None, parse_mail(raw_data)?));
+    }
+    let ix_line_end = raw_data.iter().position(|c| *c == b'\n').unwrap_or(raw_data.len());
+    let ix_mail = (ix_line_end + 1).min(raw_data.len());
+    let line = String::from_utf8_lossy(&raw_data[5..ix_line_end]);
+    let line = line.trim();
+    let (sender, date) = match line.find(char::is_whitespace) {
+        Some(ix) => (&line[..ix], line[ix..].trim_start()),
+        None => (line, ""),
+    };
+    let envelope = MboxEnvelope {
+        sender: sender.to_string(),
+        date: date.to_string(),
+    };
+    let parsed = parse_mail(&raw_data[ix_mail..]).map_err(|e| e.offset_by(ix_mail))?;
+    Ok((Some(envelope), parsed))
+}
+
 /// Used to store params for content-type and content-disposition
 struct ParamContent {
     value: String,
@@ -802,24 +3679,36 @@ struct ParamContent {
 /// significant changes to be fully correct. For instance,
 /// it does not handle quoted parameter values containing the
 /// semicolon (';') character. It also produces a BTreeMap,
-/// which implicitly does not support multiple parameters with
-/// the same key. The format for parameterized header values
-/// doesn't appear to be strongly specified anywhere.
+/// which does not support multiple parameters with the same key;
+/// duplicates are malformed, and only the first occurrence is kept.
+/// Letting a later parameter win would allow e.g. a second boundary
+/// parameter appended to the header to change how the body is split.
+/// The format for parameterized header values doesn't appear to be
+/// strongly specified anywhere.
 fn parse_param_content(content: &str) -> ParamContent {
     let mut tokens = content.split(';');
     // There must be at least one token produced by split, even if it's empty.
     let value = tokens.next().unwrap().trim();
-    let map = tokens
-        .filter_map(|kv| {
-            kv.find('=').map(|idx| {
-                let key = kv[0..idx].trim().to_lowercase();
-                let mut value = kv[idx + 1..].trim();
-                if value.starts_with('"') && value.ends_with('"') {
-                    value = &value[1..value.len() - 1];
-                }
-                (key, value.to_string())
-            })
-        }).collect();
+    let mut map = BTreeMap::new();
+    for kv in tokens {
+        if let Some(idx) = kv.find('=') {
+            let key = kv[0..idx].trim().to_lowercase();
+            if map.contains_key(&key) {
+                continue;
+            }
+            let mut value = kv[idx + 1..].trim();
+            if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                value = &value[1..value.len() - 1];
+            }
+            // Only filenames are decoded, as other values (e.g. a boundary)
+            // may legitimately look like an encoded-word
+            let decoded = match key.as_str() {
+                "name" | "filename" => decode_param_words(value),
+                _ => None,
+            };
+            map.insert(key, decoded.unwrap_or_else(|| value.to_string()));
+        }
+    }
 
     ParamContent {
         value: value.into(),
@@ -827,6 +3716,74 @@ fn parse_param_content(content: &str) -> ParamContent {
     }
 }
 
+/// Remove the line breaks inside quoted strings in a raw header value, along
+/// with the whitespace at the start of the continuation lines. Returns None
+/// if there are no such line breaks.
+fn join_quoted_folds(value: &[u8]) -> Option<Vec<u8>> {
+    let mut joined = Vec::with_capacity(value.len());
+    let mut in_quotes = false;
+    let mut changed = false;
+    let mut bytes = value.iter().peekable();
+    while let Some(&c) = bytes.next() {
+        if in_quotes && (c == b'\r' || c == b'\n') {
+            while bytes.next_if(|c| matches!(c, b'\r' | b'\n' | b' ' | b'\t')).is_some() {}
+            changed = true;
+            continue;
+        }
+        joined.push(c);
+        if c == b'"' {
+            in_quotes = !in_quotes;
+        } else if c == b'\\' && in_quotes {
+            joined.extend(bytes.next_if(|c| !matches!(c, b'\r' | b'\n')));
+        }
+    }
+    if changed {
+        Some(joined)
+    } else {
+        None
+    }
+}
+
+/// Decode the RFC 2047 encoded-word at the start of the value, regardless of
+/// what follows it, and return the value with the word replaced by its
+/// decoded text. Returns None if the value doesn't start with an encoded-word
+/// that can be decoded.
+fn decode_leading_word(value: &str) -> Option<String> {
+    let rest = value.trim_start().strip_prefix("=?")?;
+    let ix_text = rest.match_indices('?').nth(1)?.0 + 1;
+    let ix_end = find_from(rest, ix_text, "?=")?;
+    let decoded = decode_word(
+        &rest[..ix_end],
+        encoding::DecoderTrap::Replace,
+        quoted_printable::ParseMode::Robust,
+    );
+    Some(decoded.ok()?? + &rest[ix_end + 2..])
+}
+
+/// Decode a parameter value that consists entirely of RFC 2047 encoded-words,
+/// as some mailers produce for non-ASCII filenames even though RFC 2047 does
+/// not allow it. The whitespace between adjacent encoded-words is dropped.
+/// Returns None if the value is anything else, in which case it should be
+/// used literally.
+fn decode_param_words(value: &str) -> Option<String> {
+    if !value.starts_with("=?") || !value.ends_with("?=") {
+        return None;
+    }
+    let mut result = String::new();
+    for word in value.split_whitespace() {
+        if word.len() < 4 || !word.starts_with("=?") || !word.ends_with("?=") {
+            return None;
+        }
+        let decoded = decode_word(
+            &word[2..word.len() - 2],
+            encoding::DecoderTrap::Replace,
+            quoted_printable::ParseMode::Robust,
+        );
+        result.push_str(&decoded.ok()??);
+    }
+    Some(result)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -845,14 +3802,41 @@ mod tests {
         let (parsed, _) = parse_header(b"Key: Value").unwrap();
         assert_eq!(parsed.key, b"Key");
         assert_eq!(parsed.get_key().unwrap(), "Key");
+        assert_eq!(parsed.clone(), MailHeader { key: b"Key", value: b"Value" });
+        assert!(parsed.is_valid_key());
         assert_eq!(parsed.value, b"Value");
         assert_eq!(parsed.get_value().unwrap(), "Value");
 
         let (parsed, _) = parse_header(b"Key :  Value ").unwrap();
         assert_eq!(parsed.key, b"Key ");
+        assert!(parsed.key_is("key"));
+        assert!(parsed.key_is("KEY"));
+        assert!(!parsed.key_is("Key "));
+        assert!(!parsed.key_is("Ke"));
+        assert!(!parsed.is_valid_key());
+
         assert_eq!(parsed.value, b"Value ");
         assert_eq!(parsed.get_value().unwrap(), "Value ");
 
+        assert_eq!(parsed.get_key_raw(), b"Key ");
+        assert_eq!(parsed.get_value_bytes(), b"Value ");
+
+        // The raw slices borrow from the input, not from the header
+        let raw = b"Key:\t Multi\r\n line\r\n";
+        let (key, value) = {
+            let (parsed, _) = parse_header(raw).unwrap();
+            (parsed.get_key_raw(), parsed.get_value_bytes())
+        };
+        assert_eq!(key, b"Key");
+        assert_eq!(value, b"\t Multi\r\n line");
+
+        let (parsed, _) = parse_header(b"K\xe9y: Value").unwrap();
+        assert_eq!(parsed.get_key_raw(), b"K\xe9y");
+        assert!(!parsed.is_valid_key());
+        assert!(parsed.key_is("K\u{e9}Y"));
+        assert!(!parsed.key_is("K\u{c9}y"));
+        assert!(!parsed.key_is("Kay"));
+
         let (parsed, _) = parse_header(b"Key:").unwrap();
         assert_eq!(parsed.key, b"Key");
         assert_eq!(parsed.value, b"");
@@ -860,6 +3844,19 @@ mod tests {
         let (parsed, _) = parse_header(b":\n").unwrap();
         assert_eq!(parsed.key, b"");
         assert_eq!(parsed.value, b"");
+        assert!(!parsed.is_valid_key());
+
+        // The key ends at the first colon, even inside quotes
+        let (parsed, _) = parse_header(b"Received: from x (y: z); 1 Jan 2000").unwrap();
+        assert_eq!(parsed.key, b"Received");
+        assert_eq!(parsed.value, b"from x (y: z); 1 Jan 2000");
+        let (parsed, ix) = parse_header(b"\"X:Y\": value\n").unwrap();
+        assert_eq!(parsed.key, b"\"X");
+        assert_eq!(parsed.value, b"Y\": value");
+        assert_eq!(ix, 13);
+        let (parsed, _) = parse_header(b"Key::\n").unwrap();
+        assert_eq!(parsed.key, b"Key");
+        assert_eq!(parsed.get_value().unwrap(), ":");
 
         let (parsed, _) = parse_header(b"Key:Multi-line\n value").unwrap();
         assert_eq!(parsed.key, b"Key");
@@ -871,6 +3868,32 @@ mod tests {
         assert_eq!(parsed.value, b"Multi\n  line\n value");
         assert_eq!(parsed.get_value().unwrap(), "Multi line value");
 
+        let (parsed, _) = parse_header(b"Key:Multi-line\r\n value\r\n").unwrap();
+        assert_eq!(parsed.key, b"Key");
+        assert_eq!(parsed.value, b"Multi-line\r\n value");
+        assert_eq!(parsed.get_value().unwrap(), "Multi-line value");
+
+        let (parsed, _) = parse_header(b"Key:  Multi\r\n  line\r\n\tvalue\r\nNext: x").unwrap();
+        assert_eq!(parsed.key, b"Key");
+        assert_eq!(parsed.value, b"Multi\r\n  line\r\n\tvalue");
+        assert_eq!(parsed.get_value().unwrap(), "Multi line value");
+
+        let (parsed, ix) = parse_header(b"Key: Multi\r line\r\tvalue\rNext: x").unwrap();
+        assert_eq!(parsed.value, b"Multi\r line\r\tvalue");
+        assert_eq!(parsed.get_value().unwrap(), "Multi line value");
+        assert_eq!(ix, 24);
+
+        let (parsed, _) = parse_header(b"Key: Mixed\r line\n\tendings\r\n value\n").unwrap();
+        assert_eq!(parsed.value, b"Mixed\r line\n\tendings\r\n value");
+        assert_eq!(parsed.get_value().unwrap(), "Mixed line endings value");
+        assert_eq!(parsed.raw_value_lines().collect::<Vec<_>>(),
+                   vec![&b"Mixed"[..], b" line", b"\tendings", b" value"]);
+
+        let (parsed, _) = parse_header(b"Key:\n value").unwrap();
+        assert_eq!(parsed.raw_value_lines().collect::<Vec<_>>(), vec![&b""[..], b" value"]);
+        let (parsed, _) = parse_header(b"Key:").unwrap();
+        assert_eq!(parsed.raw_value_lines().collect::<Vec<_>>(), vec![&b""[..]]);
+
         let (parsed, _) = parse_header(b"Key: One\nKey2: Two").unwrap();
         assert_eq!(parsed.key, b"Key");
         assert_eq!(parsed.value, b"One");
@@ -885,9 +3908,82 @@ mod tests {
         assert_eq!(parsed.value, b"VIAGRA \xAE");
         assert_eq!(parsed.get_value().unwrap(), "VIAGRA \u{ae}");
 
-        parse_header(b" Leading: Space").unwrap_err();
-        parse_header(b"Just a string").unwrap_err();
-        parse_header(b"Key\nBroken: Value").unwrap_err();
+        assert_match!(parse_header(b"").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::EmptyInput, 0));
+        assert_match!(parse_header(b" Leading: Space").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::SpaceBeforeHeader, 0));
+        assert_match!(parse_header(b"Just a string").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::MissingColon, 0));
+        assert_match!(parse_header(b"Key\nBroken: Value").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NewlineInKey, 3));
+    }
+
+    #[test]
+    fn parse_header_offsets() {
+        let check = |raw: &[u8], next: &[u8]| {
+            let (_, ix) = parse_header(raw).unwrap();
+            assert_eq!(&raw[ix..], next, "for {:?}", String::from_utf8_lossy(raw));
+        };
+        check(b"Key: Value\nNext: x", b"Next: x");
+        check(b"Key: Value\r\nNext: x", b"Next: x");
+        check(b"Key: Value\rNext: x", b"Next: x");
+        check(b"Key: Value\n\nBody", b"\nBody");
+        check(b"Key: Value\r\n\r\nBody", b"\r\nBody");
+        check(b"Key: Multi\n line\n\tvalue\nNext: x", b"Next: x");
+        check(b"Key:\nNext: x", b"Next: x");
+        // The last header, with and without a line terminator
+        check(b"Key: Value", b"");
+        check(b"Key: Value\n", b"");
+        check(b"Key: Value\r\n", b"");
+        check(b"Key: Value\r", b"");
+        check(b"Key: Value\n ", b"");
+        check(b"Key:", b"");
+    }
+
+    #[test]
+    fn parse_value_tokens() {
+        let values: &[&[u8]] = &[
+            b"Subject: plain text",
+            b"Subject: =?iso-8859-1?Q?=A1Hola,?=\n =?iso-8859-1?Q?_se=F1or!?=",
+            b"Subject: a =?utf-8?Q?b?=\r\n c",
+            b"Subject: =?utf-8*en?Q?x?= =?bogus?Q?y?= =?utf-8?Q?unterminated",
+            b"Subject: \"=?utf-8?B?aGk=?=\"",
+            b"Subject:",
+        ];
+        for raw in values {
+            let (parsed, _) = parse_header(raw).unwrap();
+            let tokens = parsed.value_tokens().unwrap();
+            let text: String = tokens.iter().map(HeaderToken::text).collect();
+            assert_eq!(text, parsed.get_value().unwrap());
+        }
+
+        let (parsed, _) = parse_header(b"Subject: a =?utf-8?Q?b?=\r\n =?UTF-8*de?B?Yw==?= d e").unwrap();
+        assert_eq!(parsed.value_tokens().unwrap(), vec![
+            HeaderToken::Literal("a ".to_string()),
+            HeaderToken::EncodedWord { charset: "utf-8".to_string(), text: "b".to_string() },
+            HeaderToken::EncodedWord { charset: "UTF-8".to_string(), text: "c".to_string() },
+            HeaderToken::Literal(" d e".to_string()),
+        ]);
+        let (parsed, _) = parse_header(b"Subject: =?bogus?Q?y?= x").unwrap();
+        assert_eq!(parsed.value_tokens().unwrap(), vec![HeaderToken::Literal("=?bogus?Q?y?= x".to_string())]);
+        let (parsed, _) = parse_header(b"Subject:").unwrap();
+        assert!(parsed.value_tokens().unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_raw_charset_headers() {
+        let (parsed, _) = parse_header("Subject: \u{41f}\u{440}\u{438}\u{432}\u{435}\u{442}\r\n \u{2603}".as_bytes()).unwrap();
+        assert_eq!(parsed.get_value_charset("UTF-8").unwrap(),
+                   "\u{41f}\u{440}\u{438}\u{432}\u{435}\u{442} \u{2603}");
+        assert_eq!(parsed.get_value_charset("x-no-such-charset").unwrap(), parsed.get_value().unwrap());
+
+        let (parsed, _) = parse_header(b"Subject: \xf0\xd2\xc9\xd7\xc5\xd4").unwrap();
+        assert_eq!(parsed.get_value_charset("koi8-r").unwrap(),
+                   "\u{41f}\u{440}\u{438}\u{432}\u{435}\u{442}");
+        assert_eq!(parsed.get_value_charset("utf-8").unwrap(), "\u{fffd}".repeat(6));
+
+        let (parsed, _) = parse_header(b"Subject: \xe9 =?iso-8859-1?Q?=E9?=").unwrap();
+        assert_eq!(parsed.get_value_charset("iso-8859-1").unwrap(), "\u{e9} \u{e9}");
     }
 
     #[test]
@@ -910,6 +4006,42 @@ mod tests {
         let (parsed, _) = parse_header(b"HelloWorld: =?utf-8?B?aGVsbG8gd29ybGQ=?=").unwrap();
         assert_eq!(parsed.get_value().unwrap(), "hello world");
 
+        // "??>" with the standard and URL-safe alphabets
+        let (parsed, _) = parse_header(b"Std: =?utf-8?B?Pz8+?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "??>");
+        let (parsed, _) = parse_header(b"UrlSafe: =?utf-8?B?Pz8-?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "??>");
+
+        // "\u{4e2d}\u{6587}" in GB2312 and Big5, and "\u{d55c}\u{ad6d}\u{c5b4}" in EUC-KR
+        let (parsed, _) = parse_header(b"Subject: =?gb2312?B?1tDOxA==?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{4e2d}\u{6587}");
+        let (parsed, _) = parse_header(b"Subject: =?GB_2312-80?Q?=D6=D0=CE=C4?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{4e2d}\u{6587}");
+        let (parsed, _) = parse_header(b"Subject: =?EUC-CN?B?1tDOxA==?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{4e2d}\u{6587}");
+        let (parsed, _) = parse_header(b"Subject: =?big5?B?pKSk5Q==?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{4e2d}\u{6587}");
+        let (parsed, _) = parse_header(b"Subject: =?Big-5?Q?=A4=A4=A4=E5?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{4e2d}\u{6587}");
+        let (parsed, _) = parse_header(b"Subject: =?ks_c_5601?B?x9Gxub7u?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{d55c}\u{ad6d}\u{c5b4}");
+
+        let (parsed, _) = parse_header(b"Language: =?utf-8*en?B?aGk=?= =?ISO-8859-1*fr-CA?Q?=E9t=E9?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "hi \u{e9}t\u{e9}");
+        let (parsed, _) = parse_header(b"Language: =?*en?B?aGk=?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "=?*en?B?aGk=?=");
+
+        // Each encoded-word is decoded with its own charset
+        let (parsed, _) = parse_header(b"Mixed: =?iso-8859-1?Q?caf=E9?=\r\n =?utf-8?B?4oKs?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "caf\u{e9}\u{20ac}");
+        let (parsed, _) = parse_header(b"Mixed: =?utf-8?B?4oKs?= =?iso-8859-1?Q?=E9?= =?windows-1251?B?z/Do4uXy?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{20ac} \u{e9} \u{41f}\u{440}\u{438}\u{432}\u{435}\u{442}");
+        let (parsed, _) = parse_header(b"Mixed: =?koi8-r?Q?=F0=D2=C9=D7=C5=D4?=\n =?gb2312?B?1tDOxA==?=\n =?iso-8859-1?Q?=E9?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(),
+                   "\u{41f}\u{440}\u{438}\u{432}\u{435}\u{442}\u{4e2d}\u{6587}\u{e9}");
+        let (parsed, _) = parse_header(b"Mixed: =?iso-8859-1?Q?=E9?= =?x-bogus?Q?=E9?= =?utf-8?Q?=E9?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{e9} =?x-bogus?Q?=E9?= \u{fffd}");
+
         let (parsed, _) = parse_header(b"Empty: =?utf-8?Q??=").unwrap();
         assert_eq!(parsed.get_value().unwrap(), "");
 
@@ -921,19 +4053,67 @@ mod tests {
 
         let (parsed, _) = parse_header(b"Invalid: =?utf-8?Q?=E2=AC?=").unwrap();
         assert_eq!(parsed.get_value().unwrap(), "\u{fffd}");
+        assert_match!(*match parsed.get_value_strict().unwrap_err() {
+                          MailParseError::HeaderValueError(_, err) => err,
+                          err => panic!("Unexpected error {:?}", err),
+                      },
+                      MailParseError::Generic(ErrorKind::InvalidEncodedWord, 0));
+        let (parsed, _) = parse_header(b"Invalid: =?x-bogus?Q?a?=\r\n =?utf-8?B?w6k=?= =?utf-8?B?/w==?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "=?x-bogus?Q?a?= \u{e9} \u{fffd}");
+        assert_match!(*match parsed.get_value_strict().unwrap_err() {
+                          MailParseError::HeaderValueError(_, err) => err,
+                          err => panic!("Unexpected error {:?}", err),
+                      },
+                      MailParseError::Generic(ErrorKind::InvalidEncodedWord, 35));
+        let (parsed, _) = parse_header("Raw: caf\u{e9} =?utf-8?B?w6k=?=".as_bytes()).unwrap();
+        assert_eq!(parsed.get_value_strict().unwrap(), parsed.get_value().unwrap());
+
+        let (parsed, _) = parse_header(b"TrailingEquals: =?utf-8?Q?abc=?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "abc=");
+        let (parsed, _) = parse_header(b"BadEscapes: =?utf-8?Q?a=4_=zz=3d=41?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "a=4 =zz=A");
+        let (parsed, _) = parse_header(b"Spaces: =?utf-8?Q?__a_=20?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "  a  ");
 
         let (parsed, _) = parse_header(b"LineBreak: =?utf-8?Q?=E2=82\n =AC?=").unwrap();
         assert_eq!(parsed.get_value().unwrap(), "=?utf-8?Q?=E2=82 =AC?=");
+        assert_eq!(parsed.get_value_tolerant().unwrap(), "\u{20ac}");
+
+        let (parsed, _) = parse_header(
+            b"LineBreak: =?utf-8?B?aGVsbG8g?=\r\n =?utf-8?B?d29y\r\n bGQ=?= again",
+        ).unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "hello =?utf-8?B?d29y bGQ=?= again");
+        assert_eq!(parsed.get_value_tolerant().unwrap(), "hello world again");
+
+        let (parsed, _) = parse_header(b"NotAWord: =?x?y\n c").unwrap();
+        assert_eq!(parsed.get_value_tolerant().unwrap(), "=?x?y c");
 
         let (parsed, _) = parse_header(b"NotSeparateWord: hello=?utf-8?Q?world?=").unwrap();
         assert_eq!(parsed.get_value().unwrap(), "hello=?utf-8?Q?world?=");
 
         let (parsed, _) = parse_header(b"NotSeparateWord2: =?utf-8?Q?hello?=world").unwrap();
         assert_eq!(parsed.get_value().unwrap(), "=?utf-8?Q?hello?=world");
+        assert_eq!(parsed.get_value_phrase().unwrap(), "=?utf-8?Q?hello?=world");
+
+        // In a phrase, encoded-words may be adjacent to specials
+        let (parsed, _) = parse_header(b"To: G:=?utf-8?Q?a?=,(=?utf-8?Q?b?=);=?utf-8?Q?c?=@x").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "G:=?utf-8?Q?a?=,(b);=?utf-8?Q?c?=@x");
+        assert_eq!(parsed.get_value_phrase().unwrap(), "G:a,(b);=?utf-8?Q?c?=@x");
 
         let (parsed, _) = parse_header(b"Key: \"=?utf-8?Q?value?=\"").unwrap();
         assert_eq!(parsed.get_value().unwrap(), "\"value\"");
 
+        // The encoding letter is case-insensitive
+        let (parsed, _) = parse_header(b"Subject: =?utf-8?b?aGk=?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "hi");
+        let (parsed, _) = parse_header(b"Subject: =?utf-8?q?hi?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "hi");
+        let (parsed, _) = parse_header(b"Subject: =?UTF-8?b?Y2Fmw6k=?= =?Utf-8?q?au_lait?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "caf\u{e9} au lait");
+        let (parsed, _) = parse_header(b"Subject: =?utf-8?x?hi?= =?utf-8?bq?hi?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "=?utf-8?x?hi?= =?utf-8?bq?hi?=");
+        assert_eq!(parse_content_type("text/plain; name==?utf-8?b?aGkudHh0?=").params["name"], "hi.txt");
+
         let (parsed, _) = parse_header(b"Subject: =?utf-8?q?=5BOntario_Builder=5D_Understanding_home_shopping_=E2=80=93_a_q?=\n \
                                         =?utf-8?q?uick_survey?=")
             .unwrap();
@@ -960,6 +4140,16 @@ mod tests {
             parsed.get_value().unwrap(),
             "\"Motorola Owners\u{2019} Forums\" <forums@motorola.com>"
         );
+
+        let (parsed, _) = parse_header(b"Subject: Re:  spaced\r\n\tout  =?utf-8?Q?=E2=82=AC?=\r\n  x ").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "Re:  spaced out  \u{20ac}x ");
+        assert_eq!(parsed.get_value_preserve_ws().unwrap(), "Re:  spaced\tout  \u{20ac}  x ");
+        // A bare CR ends the header like any other line break, so the "b"
+        // is not dropped but starts the next header
+        let raw = b"Subject: a\rb";
+        let (parsed, ix) = parse_header(raw).unwrap();
+        assert_eq!(parsed.get_value_preserve_ws().unwrap(), "a");
+        assert_eq!(&raw[ix..], b"b");
     }
 
     #[test]
@@ -1033,11 +4223,30 @@ mod tests {
             parsed.get_all_values("AnotherKey").unwrap(),
             vec!["AnotherValue"]
         );
+        assert_eq!(
+            parsed.headers_as_pairs().unwrap(),
+            vec![
+                ("Key".to_string(), "Value".to_string()),
+                ("AnotherKey".to_string(), "AnotherValue".to_string()),
+                ("Key".to_string(), "Value2".to_string()),
+                ("Key".to_string(), "Value3".to_string()),
+            ]
+        );
         assert_eq!(parsed.get_first_value("NoKey").unwrap(), None);
         assert_eq!(
             parsed.get_all_values("NoKey").unwrap(),
             Vec::<String>::new()
         );
+        assert_eq!(parsed.get_nth_value("key", 0).unwrap(), Some("Value".to_string()));
+        assert_eq!(parsed.get_nth_value("KEY", 2).unwrap(), Some("Value3".to_string()));
+        assert_eq!(parsed.get_nth_value("Key", 3).unwrap(), None);
+        assert_eq!(parsed.get_nth_value("AnotherKey", 0).unwrap(), Some("AnotherValue".to_string()));
+        assert_eq!(parsed.get_nth_value("NoKey", 0).unwrap(), None);
+        assert_eq!(parsed.get_first_header("key").unwrap().value, b"Value");
+        assert!(parsed.get_first_header("NoKey").is_none());
+        let values: Vec<_> = parsed.get_all_headers("KEY").iter().map(|h| h.value).collect();
+        assert_eq!(values, vec![&b"Value"[..], b"Value2", b"Value3"]);
+        assert!(parsed.get_all_headers("NoKey").is_empty());
 
         let (parsed, _) = parse_headers(b"Key: value\r\nWith: CRLF\r\n\r\nBody").unwrap();
         assert_eq!(parsed.len(), 2);
@@ -1050,119 +4259,1577 @@ mod tests {
             Some("CRLF".to_string())
         );
 
-        assert_match!(parse_headers(b"Bad\nKey").unwrap_err(), MailParseError::Generic(_));
-        assert_match!(parse_headers(b"K:V\nBad\nKey").unwrap_err(), MailParseError::Generic(_));
+        assert_match!(parse_headers(b"Bad\nKey").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NewlineInKey, 3));
+        assert_match!(parse_headers(b"K:V\nBad\nKey").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NewlineInKey, 7));
+        assert_match!(parse_headers(b"Bad\rKey: Value").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NewlineInKey, 3));
+
+        let (parsed, ix) = parse_headers(b"Key: value\rWith: CR\r\rBody").unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed.get_first_value("Key").unwrap(), Some("value".to_string()));
+        assert_eq!(parsed.get_first_value("With").unwrap(), Some("CR".to_string()));
+        assert_eq!(ix, 21);
+
+        let (parsed, ix) = parse_headers(b"Key: value\rWith: LF\nAnd: CRLF\r\n\rBody").unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed.get_first_value("With").unwrap(), Some("LF".to_string()));
+        assert_eq!(parsed.get_first_value("And").unwrap(), Some("CRLF".to_string()));
+        assert_eq!(ix, 32);
+    }
+
+    #[test]
+    fn parse_mixed_line_endings() {
+        let endings = ["\n", "\r\n", "\r"];
+        for first in &endings {
+            for last in &endings {
+                for blank in &endings {
+                    // A bare CR followed by a LF is a single CRLF line break
+                    if *last == "\r" && *blank == "\n" {
+                        continue;
+                    }
+                    let raw = format!("A: 1{}B: 2{}{}Body\r\n", first, last, blank);
+                    let (parsed, ix) = parse_headers(raw.as_bytes()).unwrap();
+                    assert_eq!(parsed.len(), 2, "for {:?}", raw);
+                    assert_eq!(parsed[0].value, b"1", "for {:?}", raw);
+                    assert_eq!(parsed[1].value, b"2", "for {:?}", raw);
+                    assert_eq!(&raw[ix..], "Body\r\n", "for {:?}", raw);
+                    let mail = parse_mail(raw.as_bytes()).unwrap();
+                    assert_eq!(mail.get_body_raw().unwrap(), b"Body\r\n", "for {:?}", raw);
+                }
+            }
+        }
+
+        let (parsed, ix) = parse_headers(b"A: 1\r\n folded\n\tagain\r\nB: 2\n\r\n\r\nBody").unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].get_value().unwrap(), "1 folded again");
+        assert_eq!(ix, 29);
+        let (parsed, ix) = parse_headers(b"A: 1\r\n\nB: 2\n").unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(ix, 7);
+        let (parsed, ix) = parse_headers(b"A: 1\r\r\nB: 2\n").unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(ix, 7);
+    }
+
+    #[test]
+    fn parse_unterminated_headers() {
+        let options = ParseOptions { require_header_terminator: true, ..Default::default() };
+
+        let (parsed, ix) = parse_headers(b"Key: Value\nTwo: Second").unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(ix, 22);
+        assert_match!(parse_headers_with_options(b"Key: Value\nTwo: Second", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::UnterminatedHeaders, 22));
+        assert_match!(parse_headers_with_options(b"Key: Value\r\n", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::UnterminatedHeaders, 12));
+        assert_match!(parse_headers_with_options(b"", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::UnterminatedHeaders, 0));
+
+        let (parsed, ix) = parse_headers_with_options(b"Key: Value\r\n\r\n", &options).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(ix, 14);
+        let (parsed, ix) = parse_headers_with_options(b"\nBody", &options).unwrap();
+        assert_eq!(parsed.len(), 0);
+        assert_eq!(ix, 1);
+    }
+
+    #[test]
+    fn test_error_location() {
+        let input = b"A: 1\nB: 2\r\nC: 3\rD\n";
+        let err = parse_headers(input).unwrap_err();
+        assert_match!(err, MailParseError::Generic(ErrorKind::NewlineInKey, 17));
+        assert_eq!(err.location(input), Some((4, 2)));
+
+        assert_eq!(MailParseError::Generic(ErrorKind::EmptyInput, 0).location(b""), Some((1, 1)));
+        assert_eq!(MailParseError::Generic(ErrorKind::EmptyInput, 2).location(b"ab"), Some((1, 3)));
+        assert_eq!(MailParseError::Generic(ErrorKind::EmptyInput, 3).location(b"ab\n"), Some((2, 1)));
+        assert_eq!(MailParseError::Generic(ErrorKind::EmptyInput, 3).location(b"ab"), None);
+        assert_eq!(MailParseError::Base64DecodeError(base64::DecodeError::InvalidLength)
+                       .location(b"ab"), None);
+    }
+
+    #[test]
+    fn parse_headers_span() {
+        let (parsed, block) = parse_headers_with_span(b"A: 1\nB: 2\n\nbody").unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(block, b"A: 1\nB: 2\n\n");
+        let (_, block) = parse_headers_with_span(b"A: 1\r\n folded\r\n\r\nbody").unwrap();
+        assert_eq!(block, b"A: 1\r\n folded\r\n\r\n");
+        let (_, block) = parse_headers_with_span(b"A: 1\r\rbody").unwrap();
+        assert_eq!(block, b"A: 1\r\r");
+        let (_, block) = parse_headers_with_span(b"A: 1").unwrap();
+        assert_eq!(block, b"A: 1");
+        let (parsed, block) = parse_headers_with_span(b"\r\nbody").unwrap();
+        assert!(parsed.is_empty());
+        assert_eq!(block, b"\r\n");
+    }
+
+    #[test]
+    fn parse_flag_headers() {
+        let options = ParseOptions { allow_flag_headers: true, ..Default::default() };
+
+        let raw = b"Subject: x\nX-Flagged\r\nTo: y\n\nBody";
+        assert_match!(parse_headers(raw).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NewlineInKey, 20));
+        let (parsed, ix) = parse_headers_with_options(raw, &options).unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[1].get_key().unwrap(), "X-Flagged");
+        assert_eq!(parsed[1].get_value().unwrap(), "");
+        assert_eq!(parsed.get_first_value("To").unwrap(), Some("y".to_string()));
+        assert_eq!(&raw[ix..], b"Body");
+
+        // A flag header may still have continuation lines
+        let (parsed, ix) = parse_header_with_options(b"Flag\n folded\nNext: x", &options).unwrap();
+        assert_eq!(parsed.key, b"Flag");
+        assert_eq!(parsed.get_value().unwrap(), "folded");
+        assert_eq!(ix, 13);
+
+        // A flag header at the end of the input
+        assert_match!(parse_header(b"Just a string").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::MissingColon, 0));
+        let (parsed, ix) = parse_header_with_options(b"Just a string", &options).unwrap();
+        assert_eq!(parsed.get_key().unwrap(), "Just a string");
+        assert_eq!(parsed.value, b"");
+        assert_eq!(ix, 13);
+
+        // Other errors are still reported
+        assert_match!(parse_header_with_options(b" Flag\n", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::SpaceBeforeHeader, 0));
+        let strict = ParseOptions { strict_keys: true, ..options };
+        assert_match!(parse_header_with_options(b"Bad flag\n", &strict).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::InvalidKeyCharacter, 3));
+    }
+
+    #[test]
+    fn parse_max_header_size() {
+        let options = ParseOptions { max_header_size: Some(10), ..Default::default() };
+
+        let (parsed, ix) = parse_header_with_options(b"A: 1\r\n 234\r\nB: 2", &options).unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "1 234");
+        assert_eq!(ix, 12);
+        assert_match!(parse_header_with_options(b"A: 1\r\n 2345\r\n", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::HeaderTooLarge, 0));
+
+        let raw = b"A: 1\nB: 1\n\t2\n\t3\n\t4\n\nBody";
+        assert_eq!(parse_headers(raw).unwrap().0.len(), 2);
+        assert_match!(parse_headers_with_options(raw, &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::HeaderTooLarge, 5));
+
+        let raw = concat!(
+            "Content-Type: multipart/mixed; boundary=b\n\n",
+            "--b\n",
+            "Subject: this subject is much too long for the limit\n\n",
+            "x\n",
+            "--b--\n").as_bytes();
+        let options = ParseOptions { max_header_size: Some(41), ..Default::default() };
+        assert_match!(parse_mail_with_options(raw, &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::HeaderTooLarge, 47));
+        let options = ParseOptions { max_header_size: Some(40), ..Default::default() };
+        assert_match!(parse_mail_with_options(raw, &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::HeaderTooLarge, 0));
+    }
+
+    #[test]
+    fn parse_strict_keys() {
+        let options = ParseOptions { strict_keys: true, ..Default::default() };
+
+        let (parsed, _) = parse_header(b":\n").unwrap();
+        assert_eq!(parsed.key, b"");
+        assert_match!(parse_header_with_options(b":\n", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::EmptyKey, 0));
+        assert_match!(parse_header_with_options(b"Key : Value", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::InvalidKeyCharacter, 3));
+        assert_match!(parse_header_with_options(b"K\x01y: Value", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::InvalidKeyCharacter, 1));
+        assert_match!(parse_header_with_options(b"K\xe9y: Value", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::InvalidKeyCharacter, 1));
+        let (parsed, _) = parse_header_with_options(b"X-Weird!~Key: Value", &options).unwrap();
+        assert_eq!(parsed.get_key().unwrap(), "X-Weird!~Key");
+
+        let (parsed, _) = parse_headers_with_options(b"A: 1\nB: 2\n\n", &options).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_match!(parse_headers_with_options(b"A: 1\nB\t: 2\n\n", &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::InvalidKeyCharacter, 6));
+    }
+
+    #[test]
+    fn test_encoded_content_type() {
+        let ctype = |raw: &str| parse_mail(raw.as_bytes()).unwrap().ctype;
+
+        let parsed = ctype("Content-Type: =?utf-8?Q?text/plain=3B_charset=3Dutf-8?=\n\nx");
+        assert_eq!(parsed, parse_content_type("text/plain; charset=utf-8"));
+        let parsed = ctype("Content-Type: =?utf-8?B?dGV4dC9odG1sOyBjaGFyc2V0PSJpc28tODg1OS0xIg==?=\n\nx");
+        assert_eq!(parsed, parse_content_type("text/html; charset=\"iso-8859-1\""));
+        let parsed = ctype("Content-Type: =?utf-8?Q?TEXT/Plain?=; charset=utf-8\n\nx");
+        assert_eq!(parsed, parse_content_type("text/plain; charset=utf-8"));
+        let parsed = ctype("Content-Type: =?utf-8?B?dGV4dC9odG1s?=;charset=utf-8\n\nx");
+        assert_eq!(parsed, parse_content_type("text/html; charset=utf-8"));
+        let parsed = ctype("Content-Type: =?bogus?Q?text/plain?=; charset=utf-8\n\nx");
+        assert_eq!(parsed.mimetype, "=?bogus?q?text/plain?=");
+
+        let mail = parse_mail(concat!(
+            "Content-Type: =?utf-8?Q?multipart/mixed;?=\n",
+            " =?utf-8?Q?_boundary=3D=22b=22?=\n\n",
+            "--b\n\npart\n--b--\n").as_bytes()).unwrap();
+        assert_eq!(mail.ctype.mimetype, "multipart/mixed");
+        assert_eq!(mail.subparts.len(), 1);
+        assert_eq!(mail.subparts[0].get_body().unwrap(), "part\n");
+    }
+
+    #[test]
+    fn test_folded_content_type() {
+        // The boundary was folded in the middle of its value
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/alternative;\r\n",
+            "\tboundary=\"----=_NextPart_000_0012_\r\n",
+            "\t01D2A3B4.C5D6E7F8\"; charset=\"utf-8\"\r\n\r\n",
+            "------=_NextPart_000_0012_01D2A3B4.C5D6E7F8\r\n\r\n",
+            "part\r\n",
+            "------=_NextPart_000_0012_01D2A3B4.C5D6E7F8--\r\n").as_bytes()).unwrap();
+        assert_eq!(mail.ctype.params["boundary"], "----=_NextPart_000_0012_01D2A3B4.C5D6E7F8");
+        assert_eq!(mail.ctype.charset, Some("utf-8".to_string()));
+        assert_eq!(mail.subparts.len(), 1);
+        assert!(!mail.is_truncated());
+
+        // Folds outside of quoted strings are unfolded as usual, and quotes
+        // can be escaped
+        let ctype = |raw: &str| parse_mail(raw.as_bytes()).unwrap().ctype;
+        let parsed = ctype("Content-Type: text/plain;\n name=\"a\\\"b\n c\";\n charset=utf-8\n\nx");
+        assert_eq!(parsed.params["name"], "a\\\"bc");
+        assert_eq!(parsed.charset, Some("utf-8".to_string()));
+    }
+
+    #[test]
+    fn test_parse_content_type() {
+        let ctype = parse_content_type("text/html; charset=utf-8");
+        assert_eq!(ctype.mimetype, "text/html");
+        assert_eq!(ctype.charset, Some("utf-8".to_string()));
+        assert_eq!(ctype.params.get("boundary"), None);
+
+        let ctype = parse_content_type(" foo/bar; x=y; charset=\"fake\" ; x2=y2");
+        assert_eq!(ctype.mimetype, "foo/bar");
+        assert_eq!(ctype.charset, Some("fake".to_string()));
+        assert_eq!(ctype.params.get("boundary"), None);
+
+        let ctype = parse_content_type(" multipart/bar; boundary=foo ");
+        assert_eq!(ctype.mimetype, "multipart/bar");
+        assert_eq!(ctype.charset, None);
+        assert_eq!(ctype.params.get("boundary").unwrap(), "foo");
+        assert_eq!(ctype, parse_content_type("Multipart/Bar;boundary=\"foo\""));
+        assert!(ctype != parse_content_type("multipart/bar; boundary=bar"));
+        assert_eq!(ctype.clone(), ctype);
+        assert_eq!(ctype.kind(), MimeKind::Multipart);
+
+        let ctype = parse_content_type("application/pdf; name=\"=?utf-8?B?w6ljb2xlLnBkZg==?=\"");
+        assert_eq!(ctype.params.get("name").unwrap(), "\u{e9}cole.pdf");
+        let ctype = parse_content_type("text/plain; name==?iso-8859-1?q?caf=E9_?= =?utf-8?Q?menu.txt?=");
+        assert_eq!(ctype.params.get("name").unwrap(), "caf\u{e9} menu.txt");
+        let ctype = parse_content_type("text/plain; name=\"=?x?y?= literal\"; other=\"=?utf-8?X?abc?=\"");
+        assert_eq!(ctype.params.get("name").unwrap(), "=?x?y?= literal");
+        assert_eq!(ctype.params.get("other").unwrap(), "=?utf-8?X?abc?=");
+        // Only filenames are decoded, a boundary may look like an encoded-word
+        let ctype = parse_content_type("multipart/mixed; boundary=\"=?utf-8?Q?y?=\"");
+        assert_eq!(ctype.params.get("boundary").unwrap(), "=?utf-8?Q?y?=");
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=\"=?utf-8?Q?y?=\"\n",
+            "\n",
+            "--=?utf-8?Q?y?=\n",
+            "\n",
+            "part\n",
+            "--=?utf-8?Q?y?=--\n").as_bytes()).unwrap();
+        assert_eq!(mail.subparts.len(), 1);
+        assert_eq!(mail.subparts[0].get_body().unwrap(), "part\n");
+        let ctype = parse_content_type("text/plain; name=\"");
+        assert_eq!(ctype.params.get("name").unwrap(), "\"");
+
+        // The first of any duplicated parameters wins
+        let ctype = parse_content_type("text/plain; charset=a; CHARSET=\"b\"; charset=c");
+        assert_eq!(ctype.charset, Some("a".to_string()));
+        assert_eq!(ctype.params.get("charset").unwrap(), "a");
+        let ctype = parse_content_type("multipart/mixed; boundary=\"one\"; x=y; Boundary=two");
+        assert_eq!(ctype.params.get("boundary").unwrap(), "one");
+        assert_eq!(ctype.params.len(), 2);
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=one; boundary=two\n\n",
+            "--one\n\n",
+            "first\n",
+            "--two\n\n",
+            "smuggled\n",
+            "--one--\n").as_bytes()).unwrap();
+        assert_eq!(mail.subparts.len(), 1);
+        assert_eq!(mail.subparts[0].get_body().unwrap(), "first\n--two\n\nsmuggled\n");
+
+        // The borrowing parser finds the same values, without lowercasing them
+        for header in &["text/html; charset=utf-8", " Multipart/Mixed;BOUNDARY=\"b; c\" ",
+                        "text/plain; charset=a; CHARSET=\"b\"; boundary=\"\"; x=y", "", "bogus; x",
+                        "text/plain; name=\""] {
+            let owned = parse_content_type(header);
+            let borrowed = parse_content_type_ref(header);
+            assert!(borrowed.mimetype_is(&owned.mimetype));
+            assert_eq!(borrowed.charset.map(str::to_string), owned.charset);
+            assert_eq!(borrowed.boundary, owned.params.get("boundary").map(String::as_str));
+        }
+        let ctype = parse_content_type_ref(" Multipart/Mixed;BOUNDARY=\"b; c\" ");
+        assert_eq!(ctype, ParsedContentTypeRef { mimetype: "Multipart/Mixed", charset: None, boundary: Some("\"b") });
+
+        assert_eq!(parse_content_type("message/rfc822").kind(), MimeKind::Message);
+        assert_eq!(parse_content_type("IMAGE/png").kind(), MimeKind::Image);
+        assert_eq!(parse_content_type("audio/mpeg").kind(), MimeKind::Audio);
+        assert_eq!(parse_content_type("video/mp4").kind(), MimeKind::Video);
+        assert_eq!(parse_content_type("application/pdf").kind(), MimeKind::Application);
+        assert_eq!(parse_content_type("bogus").kind(), MimeKind::Other("bogus".to_string()));
+        assert_eq!(parse_content_type("").kind(), MimeKind::Other("".to_string()));
+
+        let ctype = parse_content_type("application/vnd.ms-excel; name=x");
+        assert_eq!(ctype.top_level_type(), "application");
+        assert_eq!(ctype.subtype(), "vnd.ms-excel");
+        let ctype = parse_content_type("text/");
+        assert_eq!(ctype.top_level_type(), "text");
+        assert_eq!(ctype.subtype(), "");
+        let ctype = parse_content_type("");
+        assert_eq!(ctype.top_level_type(), "");
+        assert_eq!(ctype.subtype(), "");
+        assert_eq!(ParsedContentType::default().subtype(), "plain");
+    }
+
+    #[test]
+    fn test_parse_content_disposition() {
+        let dis = parse_content_disposition("inline");
+        assert_eq!(dis.disposition, DispositionType::Inline);
+        assert_eq!(dis.params.get("name"), None);
+        assert_eq!(dis.params.get("filename"), None);
+
+        let dis = parse_content_disposition(
+            " attachment; x=y; charset=\"fake\" ; x2=y2; name=\"King Joffrey.death\"",
+        );
+        assert_eq!(dis.disposition, DispositionType::Attachment);
+        assert_eq!(dis.params.get("name"), Some(&"King Joffrey.death".to_string()));
+        assert_eq!(dis.params.get("filename"), None);
+        assert_eq!(dis.clone(), parse_content_disposition(
+            "ATTACHMENT;name=\"King Joffrey.death\";x=y;charset=fake;x2=y2"));
+
+        let dis = parse_content_disposition("attachment; filename=\"=?UTF-8?Q?r=C3=A9sum=C3=A9.doc?=\"");
+        assert_eq!(dis.params.get("filename"), Some(&"r\u{e9}sum\u{e9}.doc".to_string()));
+
+        let dis = parse_content_disposition(" form-data");
+        assert_eq!(dis.disposition, DispositionType::FormData);
+        assert_eq!(dis.params.get("name"), None);
+        assert_eq!(dis.params.get("filename"), None);
+    }
+
+    #[test]
+    fn test_parse_mail() {
+        let mail = parse_mail(b"Key: value\r\n\r\nSome body stuffs").unwrap();
+        assert_eq!(mail.headers.len(), 1);
+        assert_eq!(mail.headers[0].get_key().unwrap(), "Key");
+        assert_eq!(mail.headers[0].get_value().unwrap(), "value");
+        assert_eq!(mail.ctype.mimetype, "text/plain");
+        assert_eq!(mail.ctype.charset, None);
+        assert_eq!(mail.ctype.params.get("boundary"), None);
+        assert_eq!(mail.body, b"Some body stuffs");
+        assert_eq!(mail.get_body_raw().unwrap(), b"Some body stuffs");
+        assert_eq!(mail.get_body().unwrap(), "Some body stuffs");
+        assert_eq!(mail.subparts.len(), 0);
+
+        let mail = parse_mail(
+            concat!(
+                "Content-Type: MULTIpart/alternative; bounDAry=myboundary\r\n\r\n",
+                "--myboundary\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "This is the plaintext version.\r\n",
+                "--myboundary\r\n",
+                "Content-Type: text/html;chARset=utf-8\r\n\r\n",
+                "This is the <b>HTML</b> version with fake --MYBOUNDARY.\r\n",
+                "--myboundary--").as_bytes(),
+        ).unwrap();
+        assert_eq!(mail.headers.len(), 1);
+        assert_eq!(mail.headers[0].get_key().unwrap(), "Content-Type");
+        assert_eq!(mail.ctype.mimetype, "multipart/alternative");
+        assert_eq!(mail.ctype.charset, None);
+        assert_eq!(mail.ctype.params.get("boundary").unwrap(), "myboundary");
+        assert_eq!(mail.subparts.len(), 2);
+        assert_eq!(mail.subparts[0].headers.len(), 1);
+        assert_eq!(mail.subparts[0].ctype.mimetype, "text/plain");
+        assert_eq!(mail.subparts[0].ctype.charset, None);
+        assert_eq!(mail.subparts[0].ctype.params.get("boundary"), None);
+        assert_eq!(mail.subparts[1].ctype.mimetype, "text/html");
+        assert_eq!(mail.subparts[1].ctype.charset, Some("utf-8".to_string()));
+        assert_eq!(mail.subparts[1].ctype.params.get("boundary"), None);
+
+        let mail = parse_mail(
+            b"Content-Transfer-Encoding: base64\r\n\r\naGVsbG 8gd\r\n29ybGQ=",
+        ).unwrap();
+        assert_eq!(mail.get_body_raw().unwrap(), b"hello world");
+        assert_eq!(mail.get_body().unwrap(), "hello world");
+
+        let mail = parse_mail(
+            b"Content-Type: text/plain; charset=x-unknown\r\n\r\nhello world",
+        ).unwrap();
+        assert_eq!(mail.get_body_raw().unwrap(), b"hello world");
+        assert_eq!(mail.get_body().unwrap(), "hello world");
+
+        let mail = parse_mail(b"ConTENT-tyPE: text/html\r\n\r\nhello world").unwrap();
+        assert_eq!(mail.ctype.mimetype, "text/html");
+        assert_eq!(mail.get_body_raw().unwrap(), b"hello world");
+        assert_eq!(mail.get_body().unwrap(), "hello world");
+    }
+
+    #[test]
+    fn test_identity_transfer_encodings() {
+        for enc in &["7bit", "8BIT", "binary"] {
+            let raw = format!("Content-Transfer-Encoding: {}\n\nhello world", enc);
+            let mail = parse_mail(raw.as_bytes()).unwrap();
+            assert_eq!(mail.get_body_raw().unwrap(), b"hello world");
+            assert_eq!(mail.get_body().unwrap(), "hello world");
+        }
+
+        let mail = parse_mail(
+            b"Content-Transfer-Encoding: x-bogus\n\nhello world",
+        ).unwrap();
+        assert_match!(mail.get_body_raw().unwrap_err(),
+                      MailParseError::UnsupportedTransferEncoding(_));
+        assert_match!(mail.get_body().unwrap_err(),
+                      MailParseError::UnsupportedTransferEncoding(_));
+    }
+
+    #[test]
+    fn test_body_bounded() {
+        let mail = parse_mail(b"Content-Length: 4\r\n\r\nBodyGarbage").unwrap();
+        assert_eq!(mail.get_body_bounded().unwrap(), b"Body");
+        let mail = parse_mail(b"Content-length:  0 \r\n\r\nGarbage").unwrap();
+        assert_eq!(mail.get_body_bounded().unwrap(), b"");
+        let mail = parse_mail(b"Content-Length: 100\r\n\r\nShort").unwrap();
+        assert_eq!(mail.get_body_bounded().unwrap(), b"Short");
+        let mail = parse_mail(b"Content-Length: -1\r\n\r\nBody").unwrap();
+        assert_eq!(mail.get_body_bounded().unwrap(), b"Body");
+        let mail = parse_mail(b"Subject: x\r\n\r\nBody").unwrap();
+        assert_eq!(mail.get_body_bounded().unwrap(), b"Body");
+
+        // The length applies to the encoded body
+        let mail = parse_mail(concat!(
+            "Content-Length: 8\n",
+            "Content-Transfer-Encoding: base64\n\n",
+            "aGVsbG8=d29ybGQ=").as_bytes()).unwrap();
+        assert_eq!(mail.get_body_bounded().unwrap(), b"hello");
+
+        // Multipart bodies are bounded by the boundary
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=b\n",
+            "Content-Length: 2\n\n",
+            "--b\n\npart\n--b--\n").as_bytes()).unwrap();
+        assert_eq!(mail.get_body_bounded().unwrap(), mail.get_body_raw().unwrap());
+    }
+
+    #[test]
+    fn test_transfer_encoding() {
+        let cases = [
+            ("7bit", TransferEncoding::SevenBit),
+            (" 8BIT ", TransferEncoding::EightBit),
+            ("binary", TransferEncoding::Binary),
+            ("\"base64\"", TransferEncoding::Base64),
+            ("Quoted-Printable (for the umlauts)", TransferEncoding::QuotedPrintable),
+            ("x-uue", TransferEncoding::Uuencode),
+            ("X-Bogus", TransferEncoding::Other("x-bogus".to_string())),
+            ("", TransferEncoding::Other("".to_string())),
+        ];
+        for &(value, ref expected) in &cases {
+            let raw = format!("Content-Transfer-Encoding: {}\n\nbody", value);
+            let mail = parse_mail(raw.as_bytes()).unwrap();
+            assert_eq!(mail.transfer_encoding().unwrap(), *expected);
+            let unsupported = matches!(mail.get_body_raw(),
+                                       Err(MailParseError::UnsupportedTransferEncoding(_)));
+            assert_eq!(is_transfer_encoding_supported(value), !unsupported, "for {:?}", value);
+        }
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=b\n",
+            "\n",
+            "--b\n",
+            "Content-Transfer-Encoding: base64\n",
+            "\n",
+            "aGk=\n",
+            "--b--\n").as_bytes()).unwrap();
+        assert_eq!(mail.transfer_encoding().unwrap(), TransferEncoding::SevenBit);
+        assert_eq!(mail.subparts[0].transfer_encoding().unwrap(), TransferEncoding::Base64);
+    }
+
+    #[test]
+    fn test_encode_header_word() {
+        assert_eq!(encode_header_word("", "utf-8"), "");
+        assert_eq!(encode_header_word("\u{20ac}", "bogus"), "=?utf-8?B?4oKs?=");
+        assert_eq!(encode_header_word("\u{4e2d}", "us-ascii"), "=?us-ascii?B?Pw==?=");
+
+        let check = |text: &str, charset: &str| {
+            let value = encode_header_word(text, charset);
+            for word in value.split("\r\n ") {
+                assert!(word.len() <= 75, "{:?} is too long", word);
+                // Each word must decode on its own, so no character is split
+                let decoded = decode_word(&word[2..word.len() - 2],
+                                          encoding::DecoderTrap::Strict,
+                                          quoted_printable::ParseMode::Strict).unwrap();
+                assert!(decoded.is_some(), "{:?} doesn't decode", word);
+            }
+            let raw = format!("Subject: {}", value);
+            let (header, _) = parse_header(raw.as_bytes()).unwrap();
+            assert_eq!(header.get_value().unwrap(), text);
+            value.split("\r\n ").count()
+        };
+        let text = "\u{4e2d}\u{6587}\u{1f600}a".repeat(20);
+        assert_eq!(check(&text, "utf-8"), 5);
+        assert_eq!(check(&text[..text.len() - 1], "UTF-8"), 5);
+        assert_eq!(check(&"x".repeat(300), "utf-8"), 7);
+        check(&"\u{65e5}\u{672c}\u{8a9e}\u{306e}\u{4ef6}\u{540d}".repeat(10), "iso-2022-jp");
+        check("caf\u{e9} ".repeat(30).trim_end(), "windows-1252");
+    }
+
+    #[test]
+    fn test_quoted_printable_line_breaks() {
+        let body = |raw: &str| {
+            let raw = format!("Content-Transfer-Encoding: quoted-printable\n\n{}", raw);
+            parse_mail(raw.as_bytes()).unwrap().get_body().unwrap()
+        };
+        // Hard line breaks come out as CRLF, whichever line ending the input
+        // used, and soft line breaks are removed
+        assert_eq!(body("one\r\ntwo\r\n\r\nthree\r\n"), "one\r\ntwo\r\n\r\nthree\r\n");
+        assert_eq!(body("one\ntwo\n\nthree\n"), "one\r\ntwo\r\n\r\nthree\r\n");
+        assert_eq!(body("one\r\ntwo\nthree"), "one\r\ntwo\r\nthree");
+        assert_eq!(body("soft=\r\nbreak=\nhere\r\n"), "softbreakhere\r\n");
+        assert_eq!(body("a=3D=\r\n\r\nend"), "a=\r\nend");
+
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/alternative; boundary=b\r\n\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "Content-Transfer-Encoding: quoted-printable\r\n\r\n",
+            "Line one\r\n",
+            "Line =E2=82=AC\r\n",
+            "--b--\r\n").as_bytes()).unwrap();
+        assert_eq!(mail.subparts[0].get_body().unwrap(), "Line one\r\nLine \u{20ac}\r\n");
+    }
+
+    #[test]
+    fn test_get_body_flowed() {
+        let mail = parse_mail(concat!(
+            "Content-Type: text/plain; format=Flowed; DelSp=Yes\r\n",
+            "Content-Transfer-Encoding: quoted-printable\r\n\r\n",
+            "Sp=20\r\n",
+            "lit=20\r\n",
+            "word\r\n",
+            "next\r\n").as_bytes()).unwrap();
+        assert_eq!(mail.get_body_flowed().unwrap(), "Splitword\nnext\n");
+
+        let mail = parse_mail(b"Content-Type: text/plain\r\n\r\nnot \r\nflowed\r\n").unwrap();
+        assert_eq!(mail.get_body_flowed().unwrap(), "not \r\nflowed\r\n");
+    }
+
+    #[test]
+    fn test_transfer_encoding_token() {
+        for enc in &["base64 ", "BASE64 (standard)", " Base64(x)", "\"base64\"", "base64\n\t(folded)"] {
+            let raw = format!("Content-Transfer-Encoding: {}\n\naGVsbG8=", enc);
+            let mail = parse_mail(raw.as_bytes()).unwrap();
+            assert_eq!(mail.get_body().unwrap(), "hello", "for {:?}", enc);
+        }
+        let mail = parse_mail(b"Content-Transfer-Encoding: Quoted-Printable (qp)\n\nhi=21").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "hi!");
+        let mail = parse_mail(b"Content-Transfer-Encoding: (comment only)\n\nhi").unwrap();
+        assert_match!(mail.get_body().unwrap_err(), MailParseError::UnsupportedTransferEncoding(_));
+    }
+
+    #[test]
+    fn test_body_charset_info() {
+        let check = |raw: &[u8], body: &str, charset: &str| {
+            let (decoded, used) = parse_mail(raw).unwrap().get_body_with_charset_info().unwrap();
+            assert_eq!(decoded, body);
+            assert_eq!(used, charset);
+        };
+        check(b"Subject: x\n\nplain", "plain", "ascii");
+        check(b"Content-Type: text/plain; charset=UTF-8\n\n\xe2\x82\xac", "\u{20ac}", "utf-8");
+        check(b"Content-Type: text/plain; charset=cp-1252\n\n\x80", "\u{20ac}", "windows-1252");
+        check(b"Content-Type: text/plain; charset=gb2312\n\n\xd6\xd0", "\u{4e2d}", "gbk");
+        check(b"Content-Type: text/plain; charset=x-bogus\n\nabc", "abc", "ascii");
+        check(concat!("Content-Type: text/plain; charset=utf-8\n",
+                      "Content-Transfer-Encoding: base64\n\n",
+                      "4oKs").as_bytes(), "\u{20ac}", "utf-8");
+    }
+
+    #[test]
+    fn test_shift_jis_round_trip() {
+        let text = "\u{65e5}\u{672c}\u{8a9e}";
+        let sjis = charset_encoding("Shift_JIS")
+            .unwrap()
+            .encode(text, encoding::EncoderTrap::Strict)
+            .unwrap();
+        assert_eq!(sjis, b"\x93\xfa\x96\x7b\x8c\xea");
+        for label in &["Shift_JIS", "shift_jis", "SHIFT_JIS", "\"Shift_JIS\"", "x-sjis", "MS_Kanji", "csShiftJIS", "MS932"] {
+            let mut raw = format!("Content-Type: text/plain; charset={}\n\n", label).into_bytes();
+            raw.extend_from_slice(&sjis);
+            let mail = parse_mail(&raw).unwrap();
+            assert_eq!(mail.get_body().unwrap(), text, "for charset {}", label);
+            assert_eq!(mail.get_body_with_charset_info().unwrap().1, "windows-31j");
+
+            let header = format!("Subject: =?{}?B?k/qWe4zq?=", label.trim_matches('"'));
+            let (parsed, _) = parse_header(header.as_bytes()).unwrap();
+            assert_eq!(parsed.get_value().unwrap(), text, "for charset {}", label);
+            assert_eq!(parsed.get_value_charset(label.trim_matches('"')).unwrap(), text);
+        }
+        // The charset is kept as it was written
+        let mail = parse_mail(b"Content-Type: text/plain; charset=Shift_JIS\n\n").unwrap();
+        assert_eq!(mail.ctype.charset, Some("Shift_JIS".to_string()));
+    }
+
+    #[test]
+    fn test_body_unknown_charset() {
+        for charset in &["\"\"", "\" \"", "x-unknown", "\"X-Unknown\"", "unknown"] {
+            let raw = format!("Content-Type: text/plain; charset={}\n\ncaf\u{e9}", charset);
+            let mail = parse_mail(raw.as_bytes()).unwrap();
+            assert_eq!(mail.get_body().unwrap(), "caf\u{e9}", "for charset {}", charset);
+            assert_eq!(mail.get_body_with_charset_info().unwrap().1, "utf-8");
+            // A usable default charset takes precedence
+            assert_eq!(mail.get_body_with_default_charset("latin1").unwrap(), "caf\u{c3}\u{a9}");
+        }
+        // Invalid UTF-8 is replaced rather than failing
+        let mail = parse_mail(b"Content-Type: text/plain; charset=x-unknown\n\ncaf\xe9").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "caf\u{fffd}");
+        // Other unrecognized charsets still fall back to us-ascii
+        let mail = parse_mail("Content-Type: text/plain; charset=x-bogus\n\ncaf\u{e9}".as_bytes()).unwrap();
+        assert_eq!(mail.get_body().unwrap(), "caf\u{fffd}\u{fffd}");
+    }
+
+    #[test]
+    fn test_body_bom() {
+        // A UTF-16 body with a wrong charset
+        let mail = parse_mail(b"Content-Type: text/plain; charset=iso-8859-1\n\n\xfe\xff\x00c\x00a\x00f\x00\xe9").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "caf\u{e9}");
+        assert_eq!(mail.get_body_with_charset_info().unwrap(),
+                   ("caf\u{e9}".to_string(), "utf-16be".to_string()));
+        // A UTF-8 body without a charset, which is base64-encoded
+        let mail = parse_mail(b"Content-Transfer-Encoding: base64\n\n77u/Y2Fmw6k=").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "caf\u{e9}");
+        assert_eq!(mail.get_body_with_default_charset("latin1").unwrap(), "caf\u{e9}");
+        assert_eq!(mail.get_body_raw().unwrap(), b"\xef\xbb\xbfcaf\xc3\xa9");
+        // The BOM is stripped from a borrowed body too
+        let mail = parse_mail(b"Subject: x\n\n\xef\xbb\xbfplain").unwrap();
+        assert_eq!(mail.get_body_cow().unwrap(), Cow::Borrowed("plain"));
+        let mail = parse_mail(b"Subject: x\n\n\xff\xfeo\x00k\x00").unwrap();
+        assert_eq!(mail.get_body_cow().unwrap(), "ok");
+        // Something that merely starts like a BOM is left alone
+        let mail = parse_mail(b"Content-Type: text/plain; charset=latin1\n\n\xff\xe9").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "\u{ff}\u{e9}");
+    }
+
+    #[test]
+    fn test_body_default_charset() {
+        let mail = parse_mail(b"Subject: x\n\n\xe9t\xe9").unwrap();
+        assert_eq!(mail.get_body_with_default_charset("iso-8859-1").unwrap(), "\u{e9}t\u{e9}");
+        assert_eq!(mail.get_body_with_default_charset("bogus").unwrap(), "\u{fffd}t\u{fffd}");
+        let mail = parse_mail(b"Content-Type: text/plain; charset=x-unknown\n\n\xe9t\xe9").unwrap();
+        assert_eq!(mail.get_body_with_default_charset("latin-1").unwrap(), "\u{e9}t\u{e9}");
+        // A recognized charset in the Content-Type takes precedence
+        let mail = parse_mail(b"Content-Type: text/plain; charset=utf-8\n\n\xc3\xa9").unwrap();
+        assert_eq!(mail.get_body_with_default_charset("iso-8859-1").unwrap(), "\u{e9}");
+    }
+
+    #[test]
+    fn test_strict_quoted_printable() {
+        use quoted_printable::ParseMode;
+
+        let check_header = |raw: &[u8], robust: &str, strict_ok: bool| {
+            let (parsed, _) = parse_header(raw).unwrap();
+            assert_eq!(parsed.get_value().unwrap(), robust);
+            assert_eq!(parsed.get_value_with_qp_mode(ParseMode::Robust).unwrap(), robust);
+            match parsed.get_value_with_qp_mode(ParseMode::Strict) {
+                Ok(v) => {
+                    assert!(strict_ok);
+                    assert_eq!(v, robust);
+                }
+                Err(MailParseError::HeaderValueError(key, err)) => {
+                    assert!(!strict_ok);
+                    assert_eq!(key, "Subject");
+                    assert_match!(*err, MailParseError::QuotedPrintableDecodeError(_));
+                }
+                Err(e) => panic!("Unexpected error {:?}", e),
+            }
+        };
+        check_header(b"Subject: =?utf-8?Q?caf=C3=A9_au_lait?=", "caf\u{e9} au lait", true);
+        check_header(b"Subject: =?utf-8?B?aGk=?= plain = text", "hi plain = text", true);
+        check_header(b"Subject: =?utf-8?Q?caf=c3=a9?=", "caf\u{e9}", false);
+        check_header(b"Subject: =?utf-8?Q?a=zzb?=", "a=zzb", false);
+        check_header(b"Subject: =?utf-8?Q?ab=?=", "ab=", false);
+        check_header(b"Subject: =?utf-8?Q?a\tb?=", "a\tb", false);
+
+        // The error identifies the header among many
+        let (headers, _) = parse_headers(concat!(
+            "Subject: =?utf-8?Q?fine?=\n",
+            "X-Bad :  =?utf-8?Q?caf=c3=a9?=\n",
+            "X-Good: =?utf-8?Q?=C3=A9?=\n").as_bytes()).unwrap();
+        let errors: Vec<_> = headers
+            .iter()
+            .filter_map(|h| h.get_value_with_qp_mode(ParseMode::Strict).err())
+            .collect();
+        assert_eq!(errors.len(), 1);
+        match errors[0] {
+            MailParseError::HeaderValueError(ref key, _) => assert_eq!(key, "X-Bad"),
+            ref e => panic!("Unexpected error {:?}", e),
+        }
+        assert!(errors[0].to_string().starts_with("Error in value of header X-Bad: "));
+        // The whole chain of causes can be followed
+        use std::error::Error;
+        let inner = errors[0].source().unwrap();
+        assert!(inner.to_string().starts_with("QuotedPrintable decode error: "));
+        let qp_error = inner.source().unwrap();
+        assert!(qp_error.is::<quoted_printable::QuotedPrintableError>());
+        assert!(qp_error.source().is_none());
+
+        let mail = parse_mail(concat!(
+            "Content-Transfer-Encoding: quoted-printable\n\n",
+            "caf=C3=A9 =\n",
+            "au lait").as_bytes()).unwrap();
+        assert_eq!(mail.get_body_raw_with_qp_mode(ParseMode::Strict).unwrap(),
+                   "caf\u{e9} au lait".as_bytes());
+        let mail = parse_mail(concat!(
+            "Content-Type: text/plain; charset=utf-8\n",
+            "Content-Transfer-Encoding: quoted-printable\n\n",
+            "caf=c3=a9").as_bytes()).unwrap();
+        assert_eq!(mail.get_body().unwrap(), "caf\u{e9}");
+        assert_eq!(mail.get_body_with_qp_mode(ParseMode::Robust).unwrap(), "caf\u{e9}");
+        assert_match!(mail.get_body_with_qp_mode(ParseMode::Strict).unwrap_err(),
+                      MailParseError::QuotedPrintableDecodeError(_));
+    }
+
+    #[test]
+    fn test_url_safe_base64_body() {
+        // 0xfb 0xff 0xfe encodes to "+//+" in standard base64
+        let mail = parse_mail(b"Content-Transfer-Encoding: base64\n\n-__-\n+//+").unwrap();
+        assert_eq!(mail.get_body_raw().unwrap(), b"\xfb\xff\xfe\xfb\xff\xfe");
+        assert_eq!(mail.get_body_raw_lenient().unwrap(), b"\xfb\xff\xfe\xfb\xff\xfe");
+    }
+
+    #[test]
+    fn test_body_decoded_len() {
+        let check = |encoding: &str, body: &str| {
+            let raw = format!("Content-Transfer-Encoding: {}\n\n{}", encoding, body);
+            let mail = parse_mail(raw.as_bytes()).unwrap();
+            match mail.get_body_raw() {
+                Ok(decoded) => assert_eq!(mail.body_decoded_len().unwrap(), decoded.len(),
+                                          "{} body {:?}", encoding, body),
+                Err(_) => assert!(mail.body_decoded_len().is_err(), "{} body {:?}", encoding, body),
+            }
+        };
+        for body in &["", "\n", "aA==", "aGk=", "aGk", "aGVsbG8gd29ybGQ", "aGVs\r\nbG8g\r\nd29y\r\n",
+                      "aGVsbG8_-w==", "a", "aGVsb", "aG==", "aGk==", "aGVsbG8=x", "a=Gk", "aGk*",
+                      "aGVsbG8gd2=", "aGVsbG8gd29ybA=", "aGVsbG8gd29ybA==", "=", "aGVsbG8gd29="] {
+            check("base64", body);
+        }
+        for body in &["", "\n", "\r\n", "a", "a\n", "a\r\n\r\n", "a=\nb", "a=\r\nb\r\n", "a=", "a=4",
+                      "a=41", "a=4g", "a=zz\n", "trailing  \t\nspace \r\n", "=  \nx", "caf\u{e9}",
+                      "tab\there", "bare\rcr", "x\x07y\n\n\n", "a=\n=\n=", "a=41=\r\n", "a=\n",
+                      "a\n\x07", "a\n\r", "a\n\n"] {
+            check("quoted-printable", body);
+        }
+        check("7bit", "caf\u{e9}\r\n");
+        check("x-uuencode", "begin 644 cat.txt\n#0V%T\n`\nend\n");
+        check("x-bogus", "data");
+    }
+
+    #[test]
+    fn test_get_body_raw_lenient() {
+        let mail = parse_mail(
+            b"Content-Transfer-Encoding: BASE64\n\naGVsbG8g\r\n\x00d29y\r\nbG\xffQ=\r\n",
+        ).unwrap();
+        assert_match!(mail.get_body_raw().unwrap_err(), MailParseError::Base64DecodeError(_));
+        assert_eq!(mail.get_body_raw_lenient().unwrap(), b"hello world");
+
+        // A dangling character that can't make up a byte is dropped
+        let mail = parse_mail(b"Content-Transfer-Encoding: base64\n\naGVsbG8h\nX").unwrap();
+        assert_eq!(mail.get_body_raw_lenient().unwrap(), b"hello!");
+
+        let mail = parse_mail(b"Content-Transfer-Encoding: quoted-printable\n\nhi=21").unwrap();
+        assert_eq!(mail.get_body_raw_lenient().unwrap(), b"hi!");
+        let mail = parse_mail(b"Content-Transfer-Encoding: x-bogus\n\nhi").unwrap();
+        assert_match!(mail.get_body_raw_lenient().unwrap_err(),
+                      MailParseError::UnsupportedTransferEncoding(_));
+    }
+
+    #[test]
+    fn test_get_body_cow() {
+        let mail = parse_mail(b"Subject: test\n\nplain ascii").unwrap();
+        assert_match!(mail.get_body_cow().unwrap(), Cow::Borrowed("plain ascii"));
+
+        let mail = parse_mail(
+            b"Content-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\n\xe2\x82\xac",
+        ).unwrap();
+        assert_match!(mail.get_body_cow().unwrap(), Cow::Borrowed("\u{20ac}"));
+
+        // Non-ASCII data in a non-UTF-8 charset needs converting
+        let mail = parse_mail(b"Content-Type: text/plain; charset=iso-8859-1\n\n\xa1Hola!").unwrap();
+        assert_match!(mail.get_body_cow().unwrap(), Cow::Owned(_));
+        assert_eq!(mail.get_body_cow().unwrap(), "\u{a1}Hola!");
+
+        // Invalid UTF-8 gets replacement characters
+        let mail = parse_mail(b"Content-Type: text/plain; charset=utf-8\n\nbad \xe2\x82").unwrap();
+        assert_eq!(mail.get_body_cow().unwrap(), "bad \u{fffd}");
+
+        // Transfer-encoded data needs decoding
+        let mail = parse_mail(b"Content-Transfer-Encoding: base64\n\naGVsbG8=").unwrap();
+        assert_match!(mail.get_body_cow().unwrap(), Cow::Owned(_));
+        assert_eq!(mail.get_body_cow().unwrap(), "hello");
     }
 
     #[test]
-    fn test_parse_content_type() {
-        let ctype = parse_content_type("text/html; charset=utf-8");
-        assert_eq!(ctype.mimetype, "text/html");
-        assert_eq!(ctype.charset, "utf-8");
-        assert_eq!(ctype.params.get("boundary"), None);
-
-        let ctype = parse_content_type(" foo/bar; x=y; charset=\"fake\" ; x2=y2");
-        assert_eq!(ctype.mimetype, "foo/bar");
-        assert_eq!(ctype.charset, "fake");
-        assert_eq!(ctype.params.get("boundary"), None);
+    fn test_missing_terminating_boundary() {
+        let mail = parse_mail(
+            concat!(
+                "Content-Type: multipart/alternative; boundary=myboundary\r\n\r\n",
+                "--myboundary\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "part0\r\n",
+                "--myboundary\r\n",
+                "Content-Type: text/html\r\n\r\n",
+                "part1\r\n").as_bytes(),
+        ).unwrap();
+        assert_eq!(mail.subparts[0].get_body().unwrap(), "part0\r\n");
+        assert_eq!(mail.subparts[1].get_body().unwrap(), "part1\r\n");
+        assert!(mail.is_truncated());
+        assert!(!mail.subparts[1].is_truncated());
+        assert!(mail.to_owned().as_parsed_mail().is_truncated());
 
-        let ctype = parse_content_type(" multipart/bar; boundary=foo ");
-        assert_eq!(ctype.mimetype, "multipart/bar");
-        assert_eq!(ctype.charset, "us-ascii");
-        assert_eq!(ctype.params.get("boundary").unwrap(), "foo");
+        let mail = parse_mail(
+            b"Content-Type: multipart/mixed; boundary=b\n\n--b\n\npart\n--b--\n",
+        ).unwrap();
+        assert!(!mail.is_truncated());
+        let mail = parse_mail(
+            b"Content-Type: multipart/mixed; boundary=b\n\n--b\n\npart\n--b--",
+        ).unwrap();
+        assert!(!mail.is_truncated());
+        // Without any delimiter there are no parts that could have been cut off
+        let mail = parse_mail(b"Content-Type: multipart/mixed; boundary=b\n\nno parts\n").unwrap();
+        assert!(!mail.is_truncated());
+        assert!(mail.subparts.is_empty());
+        let mail = parse_mail(b"Content-Type: text/plain\n\njust text").unwrap();
+        assert!(!mail.is_truncated());
     }
 
     #[test]
-    fn test_parse_content_disposition() {
-        let dis = parse_content_disposition("inline");
-        assert_eq!(dis.disposition, DispositionType::Inline);
-        assert_eq!(dis.params.get("name"), None);
-        assert_eq!(dis.params.get("filename"), None);
+    fn test_is_multipart() {
+        let mail = parse_mail(b"Subject: plain\n\nbody").unwrap();
+        assert!(!mail.is_multipart());
+        let mail = parse_mail(b"Content-Type: multipart/mixed; boundary=b\n\nno delimiter").unwrap();
+        assert!(!mail.is_multipart());
+        let mail = parse_mail(b"Content-Type: multipart/mixed; boundary=b\n\n").unwrap();
+        assert!(!mail.is_multipart());
+        let mail = parse_mail(b"Content-Type: multipart/mixed; boundary=b\n\n--b\n\nx").unwrap();
+        assert!(mail.is_multipart());
+        assert!(mail.is_truncated());
 
-        let dis = parse_content_disposition(
-            " attachment; x=y; charset=\"fake\" ; x2=y2; name=\"King Joffrey.death\"",
-        );
-        assert_eq!(dis.disposition, DispositionType::Attachment);
-        assert_eq!(dis.params.get("name"), Some(&"King Joffrey.death".to_string()));
-        assert_eq!(dis.params.get("filename"), None);
+        let mail = parse_mail(concat!(
+            "Content-Type: message/rfc822\n\n",
+            "Content-Type: MULTIPART/ALTERNATIVE; boundary=b\n\n",
+            "--b\n\nx\n--b--\n").as_bytes()).unwrap();
+        assert!(!mail.is_multipart());
+        assert_eq!(mail.subparts.len(), 1);
+        assert!(mail.subparts[0].is_multipart());
+    }
 
-        let dis = parse_content_disposition(" form-data");
-        assert_eq!(dis.disposition, DispositionType::FormData);
-        assert_eq!(dis.params.get("name"), None);
-        assert_eq!(dis.params.get("filename"), None);
+    #[test]
+    fn test_boundary_not_at_line_start() {
+        let mail = parse_mail(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=myboundary\r\n\r\n",
+                "--myboundary\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "This part mentions --myboundary mid-line.\r\n",
+                "--myboundary\r\n",
+                "Content-Type: text/html\r\n\r\n",
+                "part1\r\n",
+                "--myboundary--\r\n").as_bytes(),
+        ).unwrap();
+        assert_eq!(mail.subparts.len(), 2);
+        assert_eq!(mail.subparts[0].get_body().unwrap(),
+                   "This part mentions --myboundary mid-line.\r\n");
+        assert_eq!(mail.subparts[1].get_body().unwrap(), "part1\r\n");
     }
 
     #[test]
-    fn test_parse_mail() {
-        let mail = parse_mail(b"Key: value\r\n\r\nSome body stuffs").unwrap();
-        assert_eq!(mail.headers.len(), 1);
-        assert_eq!(mail.headers[0].get_key().unwrap(), "Key");
-        assert_eq!(mail.headers[0].get_value().unwrap(), "value");
-        assert_eq!(mail.ctype.mimetype, "text/plain");
-        assert_eq!(mail.ctype.charset, "us-ascii");
-        assert_eq!(mail.ctype.params.get("boundary"), None);
-        assert_eq!(mail.body, b"Some body stuffs");
-        assert_eq!(mail.get_body_raw().unwrap(), b"Some body stuffs");
-        assert_eq!(mail.get_body().unwrap(), "Some body stuffs");
-        assert_eq!(mail.subparts.len(), 0);
+    fn test_boundary_at_end() {
+        let mail = parse_mail(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=b\n\n",
+                "--b\n",
+                "\n",
+                "one\n",
+                "--b\n",
+                "\n",
+                "two\n",
+                "--b").as_bytes(),
+        ).unwrap();
+        assert_eq!(mail.subparts.len(), 2);
+        assert_eq!(mail.subparts[0].get_body().unwrap(), "one\n");
+        assert_eq!(mail.subparts[1].get_body().unwrap(), "two\n");
+    }
 
+    #[test]
+    fn test_boundary_trailing_whitespace() {
         let mail = parse_mail(
             concat!(
-                "Content-Type: MULTIpart/alternative; bounDAry=myboundary\r\n\r\n",
-                "--myboundary\r\n",
+                "Content-Type: multipart/alternative; boundary=myboundary\r\n\r\n",
+                "--myboundary  \r\n",
                 "Content-Type: text/plain\r\n\r\n",
-                "This is the plaintext version.\r\n",
+                "part0\r\n",
+                "--myboundary\t\r\n",
+                "Content-Type: text/html\r\n\r\n",
+                "part1\r\n",
+                "--myboundary \t--  \r\n",
                 "--myboundary\r\n",
-                "Content-Type: text/html;chARset=utf-8\r\n\r\n",
-                "This is the <b>HTML</b> version with fake --MYBOUNDARY.\r\n",
-                "--myboundary--").as_bytes(),
+                "This epilogue is not a part\r\n").as_bytes(),
         ).unwrap();
-        assert_eq!(mail.headers.len(), 1);
-        assert_eq!(mail.headers[0].get_key().unwrap(), "Content-Type");
-        assert_eq!(mail.ctype.mimetype, "multipart/alternative");
-        assert_eq!(mail.ctype.charset, "us-ascii");
-        assert_eq!(mail.ctype.params.get("boundary").unwrap(), "myboundary");
         assert_eq!(mail.subparts.len(), 2);
-        assert_eq!(mail.subparts[0].headers.len(), 1);
-        assert_eq!(mail.subparts[0].ctype.mimetype, "text/plain");
-        assert_eq!(mail.subparts[0].ctype.charset, "us-ascii");
-        assert_eq!(mail.subparts[0].ctype.params.get("boundary"), None);
+        assert_eq!(mail.subparts[0].get_body().unwrap(), "part0\r\n");
         assert_eq!(mail.subparts[1].ctype.mimetype, "text/html");
-        assert_eq!(mail.subparts[1].ctype.charset, "utf-8");
-        assert_eq!(mail.subparts[1].ctype.params.get("boundary"), None);
+        assert_eq!(mail.subparts[1].get_body().unwrap(), "part1\r\n");
+    }
 
+    #[test]
+    fn test_boundary_with_spaces() {
         let mail = parse_mail(
-            b"Content-Transfer-Encoding: base64\r\n\r\naGVsbG 8gd\r\n29ybGQ=",
+            concat!(
+                "Content-Type: multipart/alternative;\r\n boundary= \"=_abc def \" \r\n\r\n",
+                "--=_abc def \r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "part0\r\n",
+                "--=_abc\r\n",
+                "--=_abc  def\r\n",
+                "--=_abc def \r\n",
+                "Content-Type: text/html\r\n\r\n",
+                "part1\r\n",
+                "--=_abc def --\r\n").as_bytes(),
         ).unwrap();
-        assert_eq!(mail.get_body_raw().unwrap(), b"hello world");
-        assert_eq!(mail.get_body().unwrap(), "hello world");
+        assert_eq!(mail.ctype.params.get("boundary").unwrap(), "=_abc def ");
+        assert_eq!(mail.subparts.len(), 2);
+        assert_eq!(mail.subparts[0].get_body().unwrap(),
+                   "part0\r\n--=_abc\r\n--=_abc  def\r\n");
+        assert_eq!(mail.subparts[1].ctype.mimetype, "text/html");
+        assert_eq!(mail.subparts[1].get_body().unwrap(), "part1\r\n");
+        assert!(!mail.is_truncated());
+    }
 
+    #[test]
+    fn test_boundary_prefix() {
         let mail = parse_mail(
-            b"Content-Type: text/plain; charset=x-unknown\r\n\r\nhello world",
+            concat!(
+                "Content-Type: multipart/mixed; boundary=abc\r\n\r\n",
+                "--abc\r\n",
+                "Content-Type: multipart/alternative; boundary=abc1\r\n\r\n",
+                "--abc1\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "plain\r\n",
+                "--abc1\r\n",
+                "Content-Type: text/html\r\n\r\n",
+                "<p>html</p>\r\n",
+                "--abc1--\r\n",
+                "--abc \t\r\n",
+                "Content-Type: text/plain; name=a.txt\r\n\r\n",
+                "--abcdef is not a delimiter\r\n",
+                "--abc--").as_bytes(),
         ).unwrap();
-        assert_eq!(mail.get_body_raw().unwrap(), b"hello world");
-        assert_eq!(mail.get_body().unwrap(), "hello world");
+        assert_eq!(mail.subparts.len(), 2);
+        assert!(!mail.is_truncated());
+        let inner = &mail.subparts[0];
+        assert_eq!(inner.ctype.mimetype, "multipart/alternative");
+        assert_eq!(inner.subparts.len(), 2);
+        assert!(!inner.is_truncated());
+        assert_eq!(inner.subparts[0].get_body().unwrap(), "plain\r\n");
+        assert_eq!(inner.subparts[1].get_body().unwrap(), "<p>html</p>\r\n");
+        assert_eq!(mail.subparts[1].get_body().unwrap(), "--abcdef is not a delimiter\r\n");
 
-        let mail = parse_mail(b"ConTENT-tyPE: text/html\r\n\r\nhello world").unwrap();
-        assert_eq!(mail.ctype.mimetype, "text/html");
-        assert_eq!(mail.get_body_raw().unwrap(), b"hello world");
-        assert_eq!(mail.get_body().unwrap(), "hello world");
+        // The inner boundary is a prefix of the outer one
+        let mail = parse_mail(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=abc-outer\n\n",
+                "--abc-outer\n",
+                "Content-Type: multipart/alternative; boundary=abc\n\n",
+                "--abc\n",
+                "\n",
+                "one\n",
+                "--abc--\n",
+                "--abc-outer\n",
+                "\n",
+                "two\n",
+                "--abc-outer--\n").as_bytes(),
+        ).unwrap();
+        assert_eq!(mail.subparts.len(), 2);
+        assert_eq!(mail.subparts[0].subparts.len(), 1);
+        assert_eq!(mail.subparts[0].subparts[0].get_body().unwrap(), "one\n");
+        assert_eq!(mail.subparts[1].get_body().unwrap(), "two\n");
     }
 
     #[test]
-    fn test_missing_terminating_boundary() {
+    fn test_nested_message() {
+        let mail = parse_mail(
+            concat!(
+                "Content-Type: multipart/mixed; boundary=outer\r\n\r\n",
+                "--outer\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "See the forwarded message.\r\n",
+                "--outer\r\n",
+                "Content-Type: message/rfc822\r\n\r\n",
+                "Subject: Forwarded\r\n",
+                "Content-Type: text/html\r\n\r\n",
+                "<b>Hello</b>\r\n",
+                "--outer--\r\n").as_bytes(),
+        ).unwrap();
+        assert_eq!(mail.subparts.len(), 2);
+        let forwarded = &mail.subparts[1];
+        assert_eq!(forwarded.ctype.mimetype, "message/rfc822");
+        assert_eq!(forwarded.subparts.len(), 1);
+        let inner = &forwarded.subparts[0];
+        assert_eq!(inner.headers.get_first_value("Subject").unwrap(), Some("Forwarded".to_string()));
+        assert_eq!(inner.ctype.mimetype, "text/html");
+        assert_eq!(inner.get_body().unwrap(), "<b>Hello</b>\r\n");
+
+        // Transfer-encoded messages can't be parsed in place
+        let mail = parse_mail(
+            b"Content-Type: message/global\nContent-Transfer-Encoding: base64\n\nU3ViamVjdDogaGkKCmJvZHk=",
+        ).unwrap();
+        assert_eq!(mail.subparts.len(), 0);
+        assert_eq!(mail.get_body().unwrap(), "Subject: hi\n\nbody");
+
+        let mail = parse_mail(b"Content-Type: message/global\n\nSubject: hi\n\nbody").unwrap();
+        assert_eq!(mail.subparts.len(), 1);
+        assert_eq!(mail.subparts[0].get_body().unwrap(), "body");
+
+        // A body that is not a valid message doesn't prevent parsing the rest
         let mail = parse_mail(
             concat!(
+                "Content-Type: multipart/mixed; boundary=outer\r\n\r\n",
+                "--outer\r\n",
+                "Content-Type: message/rfc822\r\n\r\n",
+                "This forwarded message has no headers at all\r\n",
+                "--outer\r\n",
+                "Content-Type: text/plain\r\n\r\n",
+                "Second part\r\n",
+                "--outer--\r\n").as_bytes(),
+        ).unwrap();
+        assert_eq!(mail.subparts.len(), 2);
+        assert_eq!(mail.subparts[0].ctype.mimetype, "message/rfc822");
+        assert!(mail.subparts[0].subparts.is_empty());
+        assert_eq!(mail.subparts[0].get_body().unwrap(),
+                   "This forwarded message has no headers at all\r\n");
+        assert_eq!(mail.subparts[1].get_body().unwrap(), "Second part\r\n");
+        let mail = parse_mail(b"Content-Type: message/rfc822\n\n Leading space").unwrap();
+        assert!(mail.subparts.is_empty());
+        assert_eq!(mail.get_body().unwrap(), " Leading space");
+
+        // The strict checks the caller asked for still apply to the nested
+        // message, just as they do at the top level
+        let raw = b"Content-Type: message/rfc822\n\nBad Key: x\n\nbody";
+        assert_eq!(parse_mail(raw).unwrap().subparts.len(), 1);
+        let options = ParseOptions { strict_keys: true, ..Default::default() };
+        assert_match!(parse_mail_with_options(raw, &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::InvalidKeyCharacter, 33));
+        let raw = b"Content-Type: message/rfc822\n\nSubject: x";
+        assert_eq!(parse_mail(raw).unwrap().subparts.len(), 1);
+        let options = ParseOptions { require_header_terminator: true, ..Default::default() };
+        assert_match!(parse_mail_with_options(raw, &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::UnterminatedHeaders, 40));
+    }
+
+    #[test]
+    fn test_to_owned() {
+        let owned = {
+            let raw = concat!(
                 "Content-Type: multipart/alternative; boundary=myboundary\r\n\r\n",
                 "--myboundary\r\n",
                 "Content-Type: text/plain\r\n\r\n",
                 "part0\r\n",
                 "--myboundary\r\n",
-                "Content-Type: text/html\r\n\r\n",
-                "part1\r\n").as_bytes(),
-        ).unwrap();
+                "Content-Type: text/html\r\n",
+                "Content-Transfer-Encoding: base64\r\n\r\n",
+                "cGFydDE=\r\n",
+                "--myboundary--\r\n").to_string();
+            parse_mail(raw.as_bytes()).unwrap().to_owned()
+        };
+        let mail = owned.as_parsed_mail();
+        assert_eq!(mail.ctype, parse_content_type("multipart/alternative; boundary=myboundary"));
+        assert_eq!(mail.ctype.mimetype, "multipart/alternative");
+        assert_eq!(mail.ctype.params.get("boundary").unwrap(), "myboundary");
+        assert_eq!(mail.subparts.len(), 2);
+        assert_eq!(mail.subparts[0].headers[0].get_key().unwrap(), "Content-Type");
         assert_eq!(mail.subparts[0].get_body().unwrap(), "part0\r\n");
-        assert_eq!(mail.subparts[1].get_body().unwrap(), "part1\r\n");
+        assert_eq!(mail.subparts[1].ctype.mimetype, "text/html");
+        assert_eq!(mail.subparts[1].get_body().unwrap(), "part1");
+    }
+
+    #[test]
+    fn test_disposition_kind() {
+        let kind = |raw: &str| parse_mail(raw.as_bytes()).unwrap().disposition_kind();
+        assert_eq!(kind("Subject: no content type\n\nHi"), DispositionKind::Body);
+        assert_eq!(kind("Content-Type: text/html\n\n<p>Hi</p>"), DispositionKind::Body);
+        assert_eq!(kind("Content-Type: multipart/mixed; boundary=b\n\n--b--"),
+                   DispositionKind::Body);
+        assert_eq!(kind("Content-Type: text/plain\nContent-Disposition: inline\n\nHi"),
+                   DispositionKind::Body);
+        assert_eq!(kind("Content-Type: text/plain\nContent-Disposition: ATTACHMENT\n\nHi"),
+                   DispositionKind::Attachment);
+        assert_eq!(kind("Content-Type: text/plain\nContent-Disposition: inline; filename=a.txt\n\nHi"),
+                   DispositionKind::Attachment);
+        assert_eq!(kind("Content-Type: application/zip; name=a.zip\n\nPK"),
+                   DispositionKind::Attachment);
+        assert_eq!(kind("Content-Type: image/png\n\nPNG"), DispositionKind::Inline);
+        assert_eq!(kind("Content-Type: image/png; name=a.png\n\nPNG"), DispositionKind::Attachment);
+        assert_eq!(kind("Content-Type: image/png; name=a.png\nContent-ID: <a>\n\nPNG"),
+                   DispositionKind::Inline);
+        assert_eq!(kind("Content-Type: image/png\nContent-Disposition: inline; filename=a.png\n\nPNG"),
+                   DispositionKind::Inline);
+        assert_eq!(kind("Content-Type: image/png\nContent-ID: <a>\nContent-Disposition: attachment\n\nPNG"),
+                   DispositionKind::Attachment);
+    }
+
+    #[test]
+    fn test_get_part_by_content_id() {
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=outer\n",
+            "Content-ID: <root@x>\n\n",
+            "--outer\n",
+            "Content-Type: multipart/related; boundary=inner\n\n",
+            "--inner\n",
+            "Content-Type: text/html\n\n",
+            "<img src=\"cid:img@x\">\n",
+            "--inner\n",
+            "Content-Type: image/gif\n",
+            "Content-ID:  < img@x > \n\n",
+            "GIF\n",
+            "--inner--\n",
+            "--outer\n",
+            "Content-Type: image/png\n",
+            "Content-ID: bare@x\n\n",
+            "PNG\n",
+            "--outer--\n").as_bytes()).unwrap();
+        assert_eq!(mail.get_part_by_content_id("root@x").unwrap().ctype.mimetype,
+                   "multipart/mixed");
+        assert_eq!(mail.get_part_by_content_id("img@x").unwrap().ctype.mimetype,
+                   "image/gif");
+        assert_eq!(mail.get_part_by_content_id("<img@x>").unwrap().ctype.mimetype,
+                   "image/gif");
+        assert_eq!(mail.get_part_by_content_id("<bare@x>").unwrap().ctype.mimetype,
+                   "image/png");
+        assert!(mail.get_part_by_content_id("IMG@x").is_none());
+        assert!(mail.get_part_by_content_id("missing@x").is_none());
+    }
+
+    #[test]
+    fn test_get_text_body() {
+        let mail = parse_mail(b"Subject: x\n\nplain body").unwrap();
+        assert_eq!(mail.get_text_body().unwrap(), Some("plain body".to_string()));
+        let mail = parse_mail(b"Content-Type: text/html\n\n<b>html</b>").unwrap();
+        assert_eq!(mail.get_text_body().unwrap(), Some("<b>html</b>".to_string()));
+
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=outer\n\n",
+            "--outer\n",
+            "Content-Type: text/plain; name=notes.txt\n\n",
+            "attached\n",
+            "--outer\n",
+            "Content-Type: multipart/alternative; boundary=inner\n\n",
+            "--inner\n",
+            "Content-Type: text/html\n\n",
+            "<p>html</p>\n",
+            "--inner\n",
+            "Content-Type: text/plain; charset=utf-8\n",
+            "Content-Transfer-Encoding: base64\n\n",
+            "Y2Fmw6kK\n",
+            "--inner--\n",
+            "--outer--\n").as_bytes()).unwrap();
+        assert_eq!(mail.get_text_body().unwrap(), Some("caf\u{e9}\n".to_string()));
+        assert_eq!(mail.get_preferred_body(&["TEXT/HTML", "text/plain"]).unwrap(),
+                   Some("<p>html</p>\n".to_string()));
+        assert_eq!(mail.get_preferred_body(&["image/png", "text/plain"]).unwrap(),
+                   Some("caf\u{e9}\n".to_string()));
+        assert_eq!(mail.get_preferred_body(&[]).unwrap(), None);
+
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=b\n\n",
+            "--b\n",
+            "Content-Type: text/plain\n",
+            "Content-Disposition: attachment\n\n",
+            "attached\n",
+            "--b\n",
+            "Content-Type: text/html\n\n",
+            "<p>html</p>\n",
+            "--b--\n").as_bytes()).unwrap();
+        assert_eq!(mail.get_text_body().unwrap(), Some("<p>html</p>\n".to_string()));
+
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=b\n\n",
+            "--b\n",
+            "Content-Type: application/pdf\n\n",
+            "PDF\n",
+            "--b--\n").as_bytes()).unwrap();
+        assert_eq!(mail.get_text_body().unwrap(), None);
+    }
+
+    #[test]
+    fn test_part_paths() {
+        // Modelled on the example in RFC 3501 section 6.4.5
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=a\n\n",
+            "--a\n",
+            "\n",
+            "1\n",
+            "--a\n",
+            "Content-Type: application/octet-stream\n\n",
+            "2\n",
+            "--a\n",
+            "Content-Type: message/rfc822\n\n",
+            "Content-Type: multipart/mixed; boundary=b\n\n",
+            "--b\n",
+            "\n",
+            "3.1\n",
+            "--b\n",
+            "Content-Type: application/octet-stream\n\n",
+            "3.2\n",
+            "--b--\n",
+            "--a\n",
+            "Content-Type: image/gif\n\n",
+            "4.1\n",
+            "--a\n",
+            "Content-Type: message/rfc822\n\n",
+            "Subject: single part\n\n",
+            "4.2.1\n",
+            "--a\n",
+            "Content-Type: multipart/mixed; boundary=c\n\n",
+            "--c\n",
+            "\n",
+            "5.1\n",
+            "--c--\n",
+            "--a--\n").as_bytes()).unwrap();
+        let paths: Vec<(String, &str)> = mail
+            .parts_with_paths()
+            .map(|(path, part)| {
+                let path: Vec<String> = path.iter().map(|n| n.to_string()).collect();
+                (path.join("."), &part.ctype.mimetype[..])
+            })
+            .collect();
+        let expected = vec![
+            ("", "multipart/mixed"),
+            ("1", "text/plain"),
+            ("2", "application/octet-stream"),
+            ("3", "message/rfc822"),
+            ("3.1", "text/plain"),
+            ("3.2", "application/octet-stream"),
+            ("4", "image/gif"),
+            ("5", "message/rfc822"),
+            ("5.1", "text/plain"),
+            ("6", "multipart/mixed"),
+            ("6.1", "text/plain"),
+        ];
+        assert_eq!(paths, expected.into_iter().map(|(p, m)| (p.to_string(), m)).collect::<Vec<_>>());
+        for (path, _) in mail.parts_with_paths() {
+            let (_, part) = mail.parts_with_paths().find(|(p, _)| *p == path).unwrap();
+            assert!(std::ptr::eq(mail.part_by_path(&path).unwrap(), part));
+        }
+
+        assert_eq!(mail.part_by_path(&[3]).unwrap().ctype.mimetype, "message/rfc822");
+        assert_eq!(mail.part_by_path(&[3, 2]).unwrap().get_body().unwrap(), "3.2\n");
+        assert_eq!(mail.part_by_path(&[5, 1]).unwrap().get_body().unwrap(), "4.2.1\n");
+        assert!(std::ptr::eq(mail.part_by_path(&[5, 1]).unwrap(), &mail.subparts[4].subparts[0]));
+        assert!(mail.part_by_path(&[1, 1]).is_none());
+        assert!(mail.part_by_path(&[5, 2]).is_none());
+        assert!(mail.part_by_path(&[7]).is_none());
+        assert!(mail.part_by_path(&[0]).is_none());
+
+        let mail = parse_mail(b"Subject: single\n\nbody").unwrap();
+        assert!(std::ptr::eq(mail.part_by_path(&[]).unwrap(), &mail));
+        assert!(std::ptr::eq(mail.part_by_path(&[1]).unwrap(), &mail));
+        assert!(mail.part_by_path(&[1, 1]).is_none());
+        assert!(mail.part_by_path(&[2]).is_none());
+        assert_eq!(mail.parts_with_paths().map(|(p, _)| p).collect::<Vec<_>>(), vec![vec![], vec![1]]);
+    }
+
+    #[test]
+    fn test_attachments() {
+        let mail = parse_mail(b"Subject: x\n\nplain body").unwrap();
+        assert!(mail.attachments().is_empty());
+        assert_eq!(mail.attachment_count(), 0);
+        let mail = parse_mail(b"Content-Type: application/pdf; name=x.pdf\n\nPDF").unwrap();
+        assert_eq!(mail.attachment_count(), 1);
+
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=outer\n\n",
+            "--outer\n",
+            "Content-Type: multipart/related; boundary=inner\n\n",
+            "--inner\n",
+            "Content-Type: text/html\n\n",
+            "<img src=\"cid:logo\">\n",
+            "--inner\n",
+            "Content-Type: image/png\n",
+            "Content-ID: <logo>\n\n",
+            "PNG\n",
+            "--inner--\n",
+            "--outer\n",
+            "Content-Type: text/plain; name=notes.txt\n\n",
+            "notes\n",
+            "--outer\n",
+            "Content-Type: message/rfc822\n",
+            "Content-Disposition: attachment\n\n",
+            "Content-Type: application/pdf; name=nested.pdf\n\n",
+            "PDF\n",
+            "--outer\n",
+            "Content-Type: image/jpeg\n",
+            "Content-Disposition: attachment; filename=photo.jpg\n\n",
+            "JPG\n",
+            "--outer--\n").as_bytes()).unwrap();
+        let mimetypes: Vec<_> = mail.attachments().iter().map(|p| &p.ctype.mimetype[..]).collect();
+        assert_eq!(mimetypes, vec!["text/plain", "message/rfc822", "image/jpeg"]);
+        assert_eq!(mail.attachment_count(), 3);
+    }
+
+    #[test]
+    fn test_signed_parts() {
+        let mail = parse_mail(concat!(
+            "Content-Type: Multipart/Signed; boundary=b; protocol=\"application/pkcs7-signature\"\n\n",
+            "--b\n",
+            "Content-Type: text/plain\n\n",
+            "content\n",
+            "--b\n",
+            "Content-Type: application/pkcs7-signature\n\n",
+            "sig\n",
+            "--b--\n").as_bytes()).unwrap();
+        assert_eq!(mail.signed_content().unwrap().ctype.mimetype, "text/plain");
+        assert_eq!(mail.signature_part().unwrap().ctype.mimetype, "application/pkcs7-signature");
+        assert_eq!(mail.ctype.params.get("protocol").unwrap(), "application/pkcs7-signature");
+
+        // Wrong number of subparts
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/signed; boundary=b\n\n",
+            "--b\n",
+            "Content-Type: text/plain\n\n",
+            "content\n",
+            "--b--\n").as_bytes()).unwrap();
+        assert!(mail.signed_content().is_none());
+        assert!(mail.signature_part().is_none());
+
+        // Not multipart/signed
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=b\n\n",
+            "--b\n\none\n",
+            "--b\n\ntwo\n",
+            "--b--\n").as_bytes()).unwrap();
+        assert!(mail.signed_content().is_none());
+        assert!(mail.signature_part().is_none());
+    }
+
+    #[test]
+    fn test_inline_images() {
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=outer\n\n",
+            "--outer\n",
+            "Content-Type: multipart/related; boundary=inner\n\n",
+            "--inner\n",
+            "Content-Type: text/html\n\n",
+            "<img src=\"cid:a\"><img src=\"cid:b\">\n",
+            "--inner\n",
+            "Content-Type: image/gif\n",
+            "Content-ID:  < a >\n\n",
+            "GIF\n",
+            "--inner\n",
+            "Content-Type: IMAGE/JPEG\n",
+            "Content-Disposition: inline\n",
+            "Content-ID: b\n",
+            "Content-Transfer-Encoding: base64\n\n",
+            "SlBH\n",
+            "--inner\n",
+            "Content-Type: image/png\n\n",
+            "no content-id\n",
+            "--inner\n",
+            "Content-Type: application/octet-stream\n",
+            "Content-ID: <blob>\n\n",
+            "not an image\n",
+            "--inner--\n",
+            "--outer\n",
+            "Content-Type: image/png\n",
+            "Content-ID: <attached>\n",
+            "Content-Disposition: attachment; filename=x.png\n\n",
+            "PNG\n",
+            "--outer--\n").as_bytes()).unwrap();
+        assert_eq!(mail.inline_images().unwrap(), vec![
+            ("a".to_string(), "image/gif".to_string(), b"GIF\n".to_vec()),
+            ("b".to_string(), "image/jpeg".to_string(), b"JPG".to_vec()),
+        ]);
+
+        let mail = parse_mail(concat!(
+            "Content-Type: image/png\n",
+            "Content-ID: <x>\n",
+            "Content-Transfer-Encoding: base64\n\n",
+            "!!!").as_bytes()).unwrap();
+        assert_match!(mail.inline_images().unwrap_err(), MailParseError::Base64DecodeError(_));
+        assert!(parse_mail(b"Subject: x\n\nbody").unwrap().inline_images().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_looks_like_html() {
+        let sniff = |body: &str| {
+            parse_mail(format!("Subject: x\n\n{}", body).as_bytes()).unwrap().looks_like_html()
+        };
+        assert!(sniff("<!DOCTYPE html>\n<html><body>Hi</body></html>"));
+        assert!(sniff("<!doctype HTML PUBLIC \"-//W3C//DTD HTML 4.01//EN\">"));
+        assert!(sniff("\r\n\t<HTML lang=en>"));
+        assert!(sniff("\u{feff}<html>"));
+        assert!(sniff("<head><title>x</title></head>"));
+        assert!(sniff("<body>\nHi\n</body>"));
+        assert!(!sniff(""));
+        assert!(!sniff("<html"));
+        assert!(!sniff("<htmlish>"));
+        assert!(!sniff("<bodyguard@example.com> wrote:"));
+        assert!(!sniff("Hi,\n<html><body>quoted page</body></html>"));
+        assert!(!sniff("<b>bold</b> but no document"));
+        assert!(!sniff("if a <html and b > c"));
+
+        let mail = parse_mail(concat!(
+            "Content-Transfer-Encoding: base64\n\n",
+            "PGh0bWw+PC9odG1sPg==\n").as_bytes()).unwrap();
+        assert!(mail.looks_like_html());
+        let mail = parse_mail(b"Content-Transfer-Encoding: base64\n\n<html>!").unwrap();
+        assert!(!mail.looks_like_html());
+    }
+
+    #[test]
+    fn test_parts_of_type() {
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=outer\n\n",
+            "--outer\n",
+            "Content-Type: multipart/related; boundary=inner\n\n",
+            "--inner\n",
+            "Content-Type: text/html\n\n",
+            "html\n",
+            "--inner\n",
+            "Content-Type: image/gif\n\n",
+            "GIF\n",
+            "--inner--\n",
+            "--outer\n",
+            "Content-Type: IMAGE/PNG\n\n",
+            "PNG\n",
+            "--outer\n\n",
+            "plain\n",
+            "--outer--\n").as_bytes()).unwrap();
+        let bodies = |mimetype| -> Vec<String> {
+            mail.parts_of_type(mimetype).iter().map(|p| p.get_body().unwrap()).collect()
+        };
+        assert_eq!(bodies("image/*"), vec!["GIF\n", "PNG\n"]);
+        assert_eq!(bodies("image/png"), vec!["PNG\n"]);
+        assert_eq!(bodies("text/*"), vec!["html\n", "plain\n"]);
+        assert_eq!(bodies(" TEXT/PLAIN "), vec!["plain\n"]);
+        assert_eq!(mail.parts_of_type("multipart/*").len(), 2);
+        assert_eq!(mail.parts_of_type("multipart/mixed")[0].subparts.len(), 3);
+        assert!(mail.parts_of_type("image/").is_empty());
+        assert!(mail.parts_of_type("video/*").is_empty());
+    }
+
+    #[test]
+    fn test_address_headers() {
+        let mail = parse_mail(concat!(
+            "From: =?utf-8?Q?Doe,_J=C3=B6rg?= <jorg@x>\n",
+            "Sender: Secretary <sec@x>\n",
+            "To: a@x, Team: b@x;\n",
+            "cc: c@x\n",
+            "CC: \"C, D\" <d@x>\n",
+            "Reply-To: <reply@x>\n",
+            "Return-Path: <>\n",
+            "Bcc: Broken <b@x\n",
+            "\n",
+            "Body").as_bytes()).unwrap();
+        let single = |display_name: Option<&str>, addr: &str| SingleInfo {
+            display_name: display_name.map(String::from),
+            addr: addr.to_string(),
+        };
+        assert_eq!(mail.headers.get_from().unwrap(),
+                   vec![MailAddr::Single(single(Some("Doe, J\u{f6}rg"), "jorg@x"))]);
+        assert_eq!(mail.headers.get_sender().unwrap(),
+                   vec![MailAddr::Single(single(Some("Secretary"), "sec@x"))]);
+        assert_eq!(mail.headers.get_to().unwrap(),
+                   vec![MailAddr::Single(single(None, "a@x")),
+                        MailAddr::Group(GroupInfo { group_name: "Team".to_string(),
+                                                    addrs: vec![single(None, "b@x")] })]);
+        assert_eq!(mail.headers.get_cc().unwrap(),
+                   vec![MailAddr::Single(single(None, "c@x")),
+                        MailAddr::Single(single(Some("C, D"), "d@x"))]);
+        assert_eq!(mail.headers.get_reply_to().unwrap(),
+                   vec![MailAddr::Single(single(None, "reply@x"))]);
+        assert_eq!(mail.headers.get_return_path().unwrap(),
+                   vec![MailAddr::Single(single(None, ""))]);
+        assert_match!(mail.headers.get_addresses("bcc").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::UnterminatedAddress, 7));
+        assert!(mail.headers.get_addresses("Resent-From").unwrap().is_empty());
+        assert_eq!(mail.headers.get_from_display_name().unwrap(), Some("Doe, J\u{f6}rg".to_string()));
+    }
+
+    #[test]
+    fn test_all_addresses() {
+        let mail = parse_mail(b"Subject: no addresses\n\nBody").unwrap();
+        assert!(mail.all_addresses().unwrap().is_empty());
+
+        let mail = parse_mail(concat!(
+            "Sender: Secretary <sec@x>\n",
+            "Reply-To: Boss <BOSS@X>\n",
+            "From: boss@x\n",
+            "To: undisclosed-recipients:;\n",
+            "Bcc: Hidden <hidden@x>, sec@x\n",
+            "Cc: a@x\n",
+            "cc: \"A\" <A@x>\n",
+            "\n",
+            "Body").as_bytes()).unwrap();
+        let addrs: Vec<_> = mail.all_addresses().unwrap().into_iter().map(|addr| match addr {
+            MailAddr::Single(info) => (info.display_name, info.addr),
+            MailAddr::Group(_) => panic!("Unexpected group"),
+        }).collect();
+        assert_eq!(addrs, vec![
+            (Some("Boss".to_string()), "boss@x".to_string()),
+            (Some("A".to_string()), "a@x".to_string()),
+            (Some("Hidden".to_string()), "hidden@x".to_string()),
+            (Some("Secretary".to_string()), "sec@x".to_string()),
+        ]);
+
+        let mail = parse_mail(b"From: a@x\nTo: Broken <b@x\n\nBody").unwrap();
+        assert_match!(mail.all_addresses().unwrap_err(),
+                      MailParseError::Generic(ErrorKind::UnterminatedAddress, _));
+    }
+
+    #[test]
+    fn test_from_display_name() {
+        let name = |raw: &str| {
+            let (headers, _) = parse_headers(raw.as_bytes()).unwrap();
+            headers.get_from_display_name().unwrap()
+        };
+        assert_eq!(name("Subject: x\n\n"), None);
+        assert_eq!(name("From: \n\n"), None);
+        assert_eq!(name("From: Empty:;\nFrom: Team: \"\" <a@x>, b@x;\n\n"), Some("a@x".to_string()));
+        assert_eq!(name("From: \"  \" <a@x>, Other <b@x>\n\n"), Some("a@x".to_string()));
+        assert_eq!(name("From: \"Doe, John\" <john@x>\n\n"), Some("Doe, John".to_string()));
+    }
+
+    #[test]
+    fn test_parse_mail_mbox() {
+        let (envelope, mail) = parse_mail_mbox(concat!(
+            "From MAILER-DAEMON  Fri Jul  8 12:08:34 2011\r\n",
+            "Subject: test\r\n\r\n",
+            "body").as_bytes()).unwrap();
+        assert_eq!(envelope, Some(MboxEnvelope {
+            sender: "MAILER-DAEMON".to_string(),
+            date: "Fri Jul  8 12:08:34 2011".to_string(),
+        }));
+        assert_eq!(mail.headers.len(), 1);
+        assert_eq!(mail.get_body().unwrap(), "body");
+
+        let (envelope, mail) = parse_mail_mbox(b"Subject: test\n\nbody").unwrap();
+        assert_eq!(envelope, None);
+        assert_eq!(mail.get_body().unwrap(), "body");
+
+        let (envelope, mail) = parse_mail_mbox(b"From someone").unwrap();
+        assert_eq!(envelope.unwrap().date, "");
+        assert!(mail.headers.is_empty());
+
+        assert_match!(parse_mail_mbox(b"From x y\n Bad: v\n").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::SpaceBeforeHeader, 9));
     }
 
     #[test]
@@ -1175,6 +5842,35 @@ mod tests {
         assert_eq!(parsed.get_body().unwrap(), "");
     }
 
+    #[test]
+    fn test_body_at_end_of_input() {
+        // The input ends right after the blank line that terminates the headers
+        let raw = b"Subject: Empty\r\n\r\n";
+        let mail = parse_mail(raw).unwrap();
+        assert_eq!(mail.headers.len(), 1);
+        assert_eq!(mail.body.len(), 0);
+        assert_eq!(mail.body.as_ptr(), raw[raw.len()..].as_ptr());
+        assert_eq!(mail.get_body_raw().unwrap(), b"");
+        assert_eq!(mail.get_body().unwrap(), "");
+        let mail = parse_mail(b"Subject: Empty\n\n").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "");
+        let mail = parse_mail(b"Content-Transfer-Encoding: base64\r\n\r\n").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "");
+        let mail = parse_mail(b"Content-Transfer-Encoding: quoted-printable\r\n\r\n").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "");
+
+        // The body has no trailing newline
+        let mail = parse_mail(b"Subject: Short\r\n\r\nNo newline").unwrap();
+        assert_eq!(mail.get_body_raw().unwrap(), b"No newline");
+        assert_eq!(mail.get_body().unwrap(), "No newline");
+        let mail = parse_mail(b"Subject: Short\r\n\r\nx").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "x");
+        let mail = parse_mail(b"Content-Transfer-Encoding: base64\r\n\r\naGk=").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "hi");
+        let mail = parse_mail(b"Content-Transfer-Encoding: quoted-printable\r\n\r\nh=69").unwrap();
+        assert_eq!(mail.get_body().unwrap(), "hi");
+    }
+
     #[test]
     fn test_no_headers_in_subpart() {
         let mail = parse_mail(
@@ -1200,11 +5896,178 @@ mod tests {
         assert_eq!(mail.get_body().unwrap(), "");
     }
 
+    #[test]
+    fn test_degenerate_input() {
+        // Inputs without any headers are accepted as long as they start with
+        // the blank line that ends the (empty) header block.
+        for &(raw, body) in &[(&b"\n"[..], &b""[..]), (b"\r\n", b""), (b"\n\n", b"\n"),
+                              (b"\r\n\r\n", b"\r\n"), (b"\nbody", b"body")] {
+            let mail = parse_mail(raw).unwrap();
+            assert!(mail.headers.is_empty());
+            assert_eq!(mail.ctype.mimetype, "text/plain");
+            assert_eq!(mail.get_body_raw().unwrap(), body);
+            assert!(mail.subparts.is_empty());
+        }
+
+        // Lines that only contain whitespace are skipped before the headers
+        for &(raw, body) in &[(&b" "[..], &b""[..]), (b"   ", b""), (b" \n", b""), (b"\t", b""),
+                              (b"\t\n\nbody", b"body"), (b" \r\n\t \r\n", b"")] {
+            let mail = parse_mail(raw).unwrap();
+            assert!(mail.headers.is_empty());
+            assert_eq!(mail.get_body_raw().unwrap(), body);
+        }
+        let mail = parse_mail(b"  \r\n\t\nSubject: x\n\n  \nbody").unwrap();
+        assert_eq!(mail.headers.get_first_value("Subject").unwrap(), Some("x".to_string()));
+        assert_eq!(mail.get_body_raw().unwrap(), b"  \nbody");
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/mixed; boundary=b\n\n",
+            "--b\n",
+            " \n",
+            "Content-Type: text/html\n\n",
+            "html\n",
+            "--b--\n").as_bytes()).unwrap();
+        assert_eq!(mail.subparts[0].ctype.mimetype, "text/html");
+
+        // Leading whitespace before a header and header-less text are reported as errors
+        assert_match!(parse_mail(b" Subject: x\n\nbody").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::SpaceBeforeHeader, 0));
+        assert_match!(parse_mail(b" \n\tSubject: x\n\nbody").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::SpaceBeforeHeader, 2));
+        assert_match!(parse_mail(b"just text").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::MissingColon, 0));
+        assert_match!(parse_mail(b"just text\n\nbody").unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NewlineInKey, 9));
+
+        // A multipart message with an empty body has no subparts
+        let mail = parse_mail(b"Content-Type: multipart/mixed; boundary=x\n\n").unwrap();
+        assert!(mail.subparts.is_empty());
+        assert_eq!(mail.get_body_raw().unwrap(), b"");
+    }
+
+    #[test]
+    fn test_malformed_input_does_not_panic() {
+        let (parsed, _) = parse_header(b"Subject: a\xc3\xa9=?x").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "a\u{c3}\u{a9}=?x");
+        let (parsed, _) = parse_header(b"Subject: a =? b").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "a =? b");
+
+        let raw = concat!(
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n\r\n",
+            "--b \r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "Content-Transfer-Encoding: base64\r\n\r\n",
+            "4oKs\r\n",
+            "--b\r\n",
+            "Content-Type: message/rfc822\r\n\r\n",
+            "Subject: =?utf-8?Q?=E2=82=AC?=\r\n\r\n",
+            "--b--\r\n").as_bytes();
+        // Every truncation, and every single-byte corruption, of a valid
+        // message must produce a result or an error rather than a panic.
+        for len in 0..raw.len() {
+            if let Ok(mail) = parse_mail(&raw[..len]) {
+                let _ = mail.get_body();
+            }
+        }
+        for ix in 0..raw.len() {
+            for &c in &[b'\n', b'-', b'=', 0xff] {
+                let mut corrupted = raw.to_vec();
+                corrupted[ix] = c;
+                if let Ok(mail) = parse_mail(&corrupted) {
+                    let _ = mail.get_body();
+                    for part in &mail.subparts {
+                        let _ = part.get_body();
+                        let _ = part.headers.get_first_value("Subject");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_digest_default_type() {
+        let mail = parse_mail(concat!(
+            "Content-Type: multipart/digest; boundary=d\n\n",
+            "--d\n\n",
+            "From: a@x\n",
+            "Subject: First\n\n",
+            "First body\n",
+            "--d\n",
+            "Content-Type: text/plain\n\n",
+            "Not a message\n",
+            "--d\n\n",
+            "Content-Type: multipart/mixed; boundary=m\n\n",
+            "--m\n\n",
+            "Plain part\n",
+            "--m--\n",
+            "--d--\n").as_bytes()).unwrap();
+        assert_eq!(mail.subparts.len(), 3);
+        let first = &mail.subparts[0];
+        assert_eq!(first.ctype.mimetype, "message/rfc822");
+        assert_eq!(first.subparts[0].headers.get_first_value("Subject").unwrap(),
+                   Some("First".to_string()));
+        assert_eq!(first.subparts[0].get_body().unwrap(), "First body\n");
+        assert_eq!(mail.subparts[1].ctype.mimetype, "text/plain");
+        assert!(mail.subparts[1].subparts.is_empty());
+        // The default only applies to the direct parts of the digest
+        let nested = &mail.subparts[2].subparts[0];
+        assert_eq!(nested.ctype.mimetype, "multipart/mixed");
+        assert_eq!(nested.subparts[0].ctype.mimetype, "text/plain");
+
+        let mail = parse_mail(b"Content-Type: multipart/mixed; boundary=m\n\n--m\n\nx\n--m--\n").unwrap();
+        assert_eq!(mail.subparts[0].ctype.mimetype, "text/plain");
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        let nested = |depth: usize| {
+            "Content-Type: message/rfc822\n\n".repeat(depth) + "Subject: x\n\nbody"
+        };
+        let raw = nested(100);
+        let mut mail = parse_mail(raw.as_bytes()).unwrap();
+        for _ in 0..100 {
+            mail = mail.subparts.into_iter().next().unwrap();
+        }
+        assert_eq!(mail.get_body().unwrap(), "body");
+        assert_match!(parse_mail(nested(101).as_bytes()).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NestingTooDeep, 3030));
+
+        // Pathological nesting is rejected rather than overflowing the stack
+        let raw = nested(100_000);
+        assert_match!(parse_mail(raw.as_bytes()).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NestingTooDeep, _));
+        let options = ParseOptions { max_depth: 0, ..Default::default() };
+        assert!(parse_mail_with_options(b"Subject: x\n\nbody", &options).is_ok());
+        assert_match!(parse_mail_with_options(nested(1).as_bytes(), &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NestingTooDeep, 30));
+
+        // Multipart nesting counts as well
+        let raw = concat!(
+            "Content-Type: multipart/mixed; boundary=b\n\n",
+            "--b\n",
+            "Content-Type: message/rfc822\n\n",
+            "Subject: inner\n\n",
+            "--b--\n");
+        let options = ParseOptions { max_depth: 1, ..Default::default() };
+        assert_match!(parse_mail_with_options(raw.as_bytes(), &options).unwrap_err(),
+                      MailParseError::Generic(ErrorKind::NestingTooDeep, 77));
+    }
+
     #[test]
     fn test_is_boundary_multibyte() {
         // Bug #26, Incorrect unwrap() guard in is_boundary()
         // 6x'REPLACEMENT CHARACTER', but 18 bytes of data:
         let test = "\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}";
-        assert!(is_boundary(test, Some(8)));
+        assert!(!is_boundary(test, Some(8)));
+        assert!(is_boundary(test, Some(18)));
+        assert!(is_boundary(test, Some(100)));
+        assert!(is_boundary(test, None));
+        // The offsets are byte offsets, not character positions
+        assert!(is_boundary("\u{e9}\u{e9} x", Some(4)));
+        assert!(!is_boundary("\u{e9}\u{e9} x", Some(5)));
+
+        let (parsed, _) = parse_header(b"Subject: \xc3\xa9 =?utf-8?Q?x?= \xc3\xa9").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{c3}\u{a9} x \u{c3}\u{a9}");
+        let (parsed, _) = parse_header(b"Subject: \xc3\xa9=?utf-8?Q?x?=").unwrap();
+        assert_eq!(parsed.get_value().unwrap(), "\u{c3}\u{a9}=?utf-8?Q?x?=");
     }
 }
@@ -0,0 +1,89 @@
+use {ErrorKind, MailParseError};
+
+/// Parse the value of a References or In-Reply-To header into the list of
+/// message-ids it contains. The angle brackets around each message-id are not
+/// included in the returned strings. The ids may be separated by whitespace
+/// or commas, and anything in parenthesized comments or quoted strings is
+/// skipped, as are any other words outside of angle brackets (some mailers
+/// put free-form text into In-Reply-To). An error is returned if a message-id
+/// is missing its closing angle bracket.
+///
+/// # Examples
+/// ```
+///     use mailparse::msgidparse;
+///     let ids = msgidparse("<abc@example.com> (first)\n <def@example.com>,<ghi@example.com>").unwrap();
+///     assert_eq!(ids, vec!["abc@example.com", "def@example.com", "ghi@example.com"]);
+/// ```
+pub fn msgidparse(header_value: &str) -> Result<Vec<String>, MailParseError> {
+    let mut ids = Vec::new();
+    let mut chars = header_value.char_indices();
+    while let Some((ix, c)) = chars.next() {
+        match c {
+            '<' => {
+                let mut id = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '>')) => break,
+                        Some((_, c)) if c.is_whitespace() => continue,
+                        Some((_, c)) => id.push(c),
+                        None => {
+                            return Err(MailParseError::Generic(ErrorKind::UnterminatedMessageId, ix))
+                        }
+                    }
+                }
+                if !id.is_empty() {
+                    ids.push(id);
+                }
+            }
+            '(' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some((_, '\\')) => {
+                            chars.next();
+                        }
+                        Some((_, '(')) => depth += 1,
+                        Some((_, ')')) => depth -= 1,
+                        Some(_) => (),
+                        None => break,
+                    }
+                }
+            }
+            '"' => loop {
+                match chars.next() {
+                    Some((_, '\\')) => {
+                        chars.next();
+                    }
+                    Some((_, '"')) | None => break,
+                    Some(_) => (),
+                }
+            },
+            _ => (),
+        }
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_message_ids() {
+        assert_eq!(msgidparse("").unwrap(), Vec::<String>::new());
+        assert_eq!(msgidparse("<one@x>").unwrap(), vec!["one@x"]);
+        assert_eq!(msgidparse("<one@x> <two@y>\r\n\t<three@z>").unwrap(),
+                   vec!["one@x", "two@y", "three@z"]);
+        assert_eq!(msgidparse("<one@x>,<two@y> , <three@z>").unwrap(),
+                   vec!["one@x", "two@y", "three@z"]);
+        assert_eq!(msgidparse("(a (nested) <comment@x>) <one@x> (\\) <bogus@x>)").unwrap(),
+                   vec!["one@x"]);
+        assert_eq!(msgidparse("Your message of \"Mon <1 Jan>\" <one@x>").unwrap(),
+                   vec!["one@x"]);
+        assert_eq!(msgidparse("<one\n @x>").unwrap(), vec!["one@x"]);
+        match msgidparse("<one@x> <two@y").unwrap_err() {
+            MailParseError::Generic(ErrorKind::UnterminatedMessageId, 8) => (),
+            e => panic!("Unexpected error {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use {ErrorKind, MailParseError, ParsedMail};
+
+/// Reassemble a message that was split into `message/partial` fragments, as
+/// described in RFC 2046 section 5.2.2. The fragments may be passed in any
+/// order; they are sorted by their `number` parameter and their bodies are
+/// concatenated. All fragments must have the same `id` parameter, and
+/// together they must cover the numbers from 1 up to the `total` given by
+/// at least one of them, without duplicates. The returned bytes form the
+/// encapsulated message, which can be passed to `parse_mail`. Note that the
+/// headers of the messages enclosing the fragments are not merged into it.
+///
+/// An `InvalidPartialFragment` error is returned for a part that is not a
+/// message/partial fragment of the same message as the first part, and a
+/// `MissingPartialFragment` error if a fragment is missing or duplicated. The
+/// offset of these errors is the index into `parts` of the offending part,
+/// or `parts.len()` if a fragment (or the `total` parameter) is missing.
+///
+/// # Examples
+/// ```
+///     use mailparse::{parse_mail, reassemble_partial};
+///     let part2 = parse_mail(concat!(
+///             "Content-Type: message/partial; id=\"abc@x\"; number=2; total=2\n",
+///             "\n",
+///             "second half").as_bytes())
+///         .unwrap();
+///     let part1 = parse_mail(concat!(
+///             "Content-Type: message/partial; id=\"abc@x\"; number=1\n",
+///             "\n",
+///             "Subject: Big message\n",
+///             "\n",
+///             "first half, ").as_bytes())
+///         .unwrap();
+///     let raw = reassemble_partial(&[part2, part1]).unwrap();
+///     let message = parse_mail(&raw).unwrap();
+///     assert_eq!(message.get_body().unwrap(), "first half, second half");
+/// ```
+pub fn reassemble_partial(parts: &[ParsedMail]) -> Result<Vec<u8>, MailParseError> {
+    let invalid = |ix| MailParseError::Generic(ErrorKind::InvalidPartialFragment, ix);
+    let missing = |ix| MailParseError::Generic(ErrorKind::MissingPartialFragment, ix);
+
+    let mut id = None;
+    let mut total = None;
+    let mut fragments = Vec::with_capacity(parts.len());
+    for (ix, part) in parts.iter().enumerate() {
+        if part.ctype.mimetype != "message/partial" {
+            return Err(invalid(ix));
+        }
+        let params = &part.ctype.params;
+        let part_id = params.get("id").ok_or_else(|| invalid(ix))?;
+        if *id.get_or_insert(part_id) != part_id {
+            return Err(invalid(ix));
+        }
+        let number = params
+            .get("number")
+            .and_then(|n| n.trim().parse::<usize>().ok())
+            .ok_or_else(|| invalid(ix))?;
+        if let Some(part_total) = params.get("total") {
+            let part_total = part_total.trim().parse::<usize>().map_err(|_| invalid(ix))?;
+            if *total.get_or_insert(part_total) != part_total {
+                return Err(invalid(ix));
+            }
+        }
+        fragments.push((number, ix, part));
+    }
+
+    let total = total.ok_or_else(|| missing(parts.len()))?;
+    fragments.sort_by_key(|&(number, _, _)| number);
+    let mut result = Vec::new();
+    for (expected, &(number, ix, part)) in (1..).zip(fragments.iter()) {
+        if number == 0 || number > total {
+            return Err(invalid(ix));
+        } else if number < expected {
+            return Err(missing(ix));
+        } else if number > expected {
+            return Err(missing(parts.len()));
+        }
+        result.extend_from_slice(&part.get_body_raw()?);
+    }
+    if fragments.len() != total {
+        return Err(missing(parts.len()));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_mail;
+
+    fn fragment(params: &str, body: &str) -> Vec<u8> {
+        format!("Content-Type: message/partial; {}\r\n\r\n{}", params, body).into_bytes()
+    }
+
+    #[test]
+    fn reassemble_fragments() {
+        let raw = [
+            fragment("id=a; number=3; total=3", "c"),
+            fragment("id=a; number=1", "Subject: x\r\n\r\na"),
+            fragment("number=2; id=a", "b"),
+        ];
+        let parts: Vec<ParsedMail> = raw.iter().map(|r| parse_mail(r).unwrap()).collect();
+        assert_eq!(reassemble_partial(&parts).unwrap(), b"Subject: x\r\n\r\nabc");
+
+        let check_err = |raw: &[Vec<u8>], expected_kind, expected_ix| {
+            let parts: Vec<ParsedMail> = raw.iter().map(|r| parse_mail(r).unwrap()).collect();
+            match reassemble_partial(&parts).unwrap_err() {
+                MailParseError::Generic(kind, ix) => {
+                    assert_eq!(kind, expected_kind);
+                    assert_eq!(ix, expected_ix);
+                }
+                e => panic!("Unexpected error {:?}", e),
+            }
+        };
+        let not_partial = b"Content-Type: text/plain\r\n\r\nx".to_vec();
+        check_err(&[fragment("id=a; number=1; total=1", "a"), not_partial],
+                  ErrorKind::InvalidPartialFragment, 1);
+        check_err(&[fragment("id=a; number=1", "a"), fragment("id=b; number=2; total=2", "b")],
+                  ErrorKind::InvalidPartialFragment, 1);
+        check_err(&[fragment("id=a; number=x; total=1", "a")],
+                  ErrorKind::InvalidPartialFragment, 0);
+        check_err(&[fragment("id=a; number=1; total=2", "a"), fragment("id=a; number=2; total=3", "b")],
+                  ErrorKind::InvalidPartialFragment, 1);
+        // Missing total, missing fragments, and duplicates
+        check_err(&[fragment("id=a; number=1", "a")], ErrorKind::MissingPartialFragment, 1);
+        check_err(&[fragment("id=a; number=2; total=2", "b")], ErrorKind::MissingPartialFragment, 1);
+        check_err(&[fragment("id=a; number=1; total=2", "a")], ErrorKind::MissingPartialFragment, 1);
+        check_err(&[fragment("id=a; number=1; total=1", "a"), fragment("id=a; number=1", "a")],
+                  ErrorKind::MissingPartialFragment, 1);
+        check_err(&[fragment("id=a; number=1; total=1", "a"), fragment("id=a; number=2", "b")],
+                  ErrorKind::InvalidPartialFragment, 1);
+        check_err(&[fragment("id=a; number=0; total=1", "a")], ErrorKind::InvalidPartialFragment, 0);
+        check_err(&[], ErrorKind::MissingPartialFragment, 0);
+    }
+}
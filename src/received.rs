@@ -0,0 +1,202 @@
+use dateparse;
+use {ErrorKind, MailParseError};
+
+/// The components of a Received header, as returned by `parse_received`.
+/// Each clause is None if it was not present in the header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReceivedHeader {
+    /// The host the message was received from, as it identified itself.
+    pub from: Option<String>,
+    /// The comment following the `from` host, if any. Receiving servers
+    /// usually put the host's reverse DNS name and IP address here, e.g.
+    /// "mail.example.com [192.0.2.1]".
+    pub from_info: Option<String>,
+    /// The host that received the message.
+    pub by: Option<String>,
+    /// The physical path the message was received over, which is rarely used.
+    pub via: Option<String>,
+    /// The protocol the message was received with, e.g. "ESMTPS".
+    pub with: Option<String>,
+    /// The id the receiving host assigned to the message.
+    pub id: Option<String>,
+    /// The recipient the message was received for, without angle brackets.
+    pub for_: Option<String>,
+    /// The time at which the message was received, as a UNIX epoch timestamp.
+    pub date: Option<i64>,
+}
+
+/// Parse the value of a Received header, as described in RFC 5321 section
+/// 4.4, into its clauses. Each clause is a keyword (`from`, `by`, `via`,
+/// `with`, `id` or `for`, in any case and order) followed by a value, and the
+/// date follows the last semicolon. Comments are skipped, except for the one
+/// right after the `from` host, and so are any words that don't belong to a
+/// clause. Only the first value of each clause is kept. An error is returned
+/// if the date can't be parsed by `dateparse`.
+///
+/// # Examples
+/// ```
+///     use mailparse::parse_received;
+///     let received = parse_received(concat!(
+///             "from mx.example.com (mx.example.com [192.0.2.1])\n",
+///             "\tby mail.example.org (Postfix) with ESMTPS id 4B2C1\n",
+///             "\tfor <bob@example.org>; Sun, 02 Oct 2016 07:06:22 -0700 (PDT)"))
+///         .unwrap();
+///     assert_eq!(received.from.unwrap(), "mx.example.com");
+///     assert_eq!(received.from_info.unwrap(), "mx.example.com [192.0.2.1]");
+///     assert_eq!(received.by.unwrap(), "mail.example.org");
+///     assert_eq!(received.with.unwrap(), "ESMTPS");
+///     assert_eq!(received.id.unwrap(), "4B2C1");
+///     assert_eq!(received.for_.unwrap(), "bob@example.org");
+///     assert_eq!(received.date, Some(1475417182));
+/// ```
+pub fn parse_received(header_value: &str) -> Result<ReceivedHeader, MailParseError> {
+    let mut received = ReceivedHeader::default();
+    let (clauses, date) = match header_value.rfind(';') {
+        Some(ix) => (&header_value[..ix], Some((ix + 1, header_value[ix + 1..].trim()))),
+        None => (header_value, None),
+    };
+    if let Some((ix, date)) = date {
+        if !date.is_empty() {
+            let timestamp = dateparse(date)
+                .map_err(|_| MailParseError::Generic(ErrorKind::InvalidDate, ix))?;
+            received.date = Some(timestamp);
+        }
+    }
+
+    let tokens = tokenize(clauses);
+    let mut tokens = tokens.iter().peekable();
+    while let Some(token) = tokens.next() {
+        let keyword = match *token {
+            Token::Word(ref word) => word.to_ascii_lowercase(),
+            Token::Comment(_) => continue,
+        };
+        let clause = match &keyword[..] {
+            "from" => &mut received.from,
+            "by" => &mut received.by,
+            "via" => &mut received.via,
+            "with" => &mut received.with,
+            "id" => &mut received.id,
+            "for" => &mut received.for_,
+            _ => continue,
+        };
+        let value = match tokens.peek() {
+            Some(Token::Word(value)) => value,
+            _ => continue,
+        };
+        tokens.next();
+        if clause.is_none() {
+            let value = value.trim_start_matches('<').trim_end_matches('>');
+            *clause = Some(value.to_string());
+            if keyword == "from" {
+                if let Some(Token::Comment(info)) = tokens.peek() {
+                    received.from_info = Some(info.clone());
+                }
+            }
+        }
+    }
+    Ok(received)
+}
+
+enum Token {
+    Word(String),
+    Comment(String),
+}
+
+/// Split the clauses of a Received header into words and (unwrapped)
+/// comments. Whitespace inside quoted strings and angle brackets doesn't end
+/// a word.
+fn tokenize(value: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => {
+                let mut comment = String::new();
+                let mut depth = 1;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            comment.extend(chars.next());
+                            continue;
+                        }
+                        '(' => depth += 1,
+                        ')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => (),
+                    }
+                    comment.push(c);
+                }
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut word = String::new();
+                let mut c = Some(c);
+                let mut closing = None;
+                while let Some(next) = c {
+                    word.push(next);
+                    if Some(next) == closing {
+                        closing = None;
+                    } else if closing.is_none() && next == '"' {
+                        closing = Some('"');
+                    } else if closing.is_none() && next == '<' {
+                        closing = Some('>');
+                    }
+                    c = match chars.peek() {
+                        Some(&c) if closing.is_none() && (c.is_whitespace() || c == '(') => None,
+                        _ => chars.next(),
+                    };
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_received_headers() {
+        assert_eq!(parse_received("").unwrap(), ReceivedHeader::default());
+
+        let received = parse_received(concat!(
+            "from [192.0.2.7] (unknown [192.0.2.7]) (Authenticated sender: a (b) c)\r\n",
+            " by smtp.example.net (Postfix) via TCP WITH esmtpsa\r\n",
+            " (using TLSv1.3 with cipher TLS_AES_256_GCM_SHA384 (256/256 bits))\r\n",
+            " ID <20161002.abc@smtp.example.net> for \"odd name\"@example.com\r\n",
+            " ; 2 Oct 2016 14:06:22 +0000")).unwrap();
+        assert_eq!(received, ReceivedHeader {
+            from: Some("[192.0.2.7]".to_string()),
+            from_info: Some("unknown [192.0.2.7]".to_string()),
+            by: Some("smtp.example.net".to_string()),
+            via: Some("TCP".to_string()),
+            with: Some("esmtpsa".to_string()),
+            id: Some("20161002.abc@smtp.example.net".to_string()),
+            for_: Some("\"odd name\"@example.com".to_string()),
+            date: Some(1475417182),
+        });
+
+        // Clauses may be missing or out of order, and keywords without a
+        // value or repeated clauses are ignored
+        let received = parse_received("by local.host with LMTP with SMTP from (x)").unwrap();
+        assert_eq!(received.by.unwrap(), "local.host");
+        assert_eq!(received.with.unwrap(), "LMTP");
+        assert_eq!(received.from, None);
+        assert_eq!(received.date, None);
+        let received = parse_received("from a.example (unterminated comment").unwrap();
+        assert_eq!(received.from.unwrap(), "a.example");
+        assert_eq!(received.from_info.unwrap(), "unterminated comment");
+
+        match parse_received("from a.example by b.example; 2 Foo 2016") {
+            Err(MailParseError::Generic(ErrorKind::InvalidDate, 28)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use serde::ser::{Error, Serialize, SerializeStruct, Serializer};
+
+use {MailHeader, ParsedContentType, ParsedMail};
+
+/// Headers are serialized as their decoded key and value.
+impl<'a> Serialize for MailHeader<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("MailHeader", 2)?;
+        state.serialize_field("key", &self.get_key().map_err(S::Error::custom)?)?;
+        state.serialize_field("value", &self.get_value().map_err(S::Error::custom)?)?;
+        state.end()
+    }
+}
+
+impl Serialize for ParsedContentType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ParsedContentType", 3)?;
+        state.serialize_field("mimetype", &self.mimetype)?;
+        state.serialize_field("charset", &self.charset)?;
+        state.serialize_field("params", &self.params)?;
+        state.end()
+    }
+}
+
+/// The body is serialized as decoded by `ParsedMail::get_body`, so an error
+/// is returned if the transfer-encoding cannot be undone.
+impl<'a> Serialize for ParsedMail<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ParsedMail", 4)?;
+        state.serialize_field("headers", &self.headers)?;
+        state.serialize_field("ctype", &self.ctype)?;
+        state.serialize_field("body", &self.get_body().map_err(S::Error::custom)?)?;
+        state.serialize_field("subparts", &self.subparts)?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use parse_mail;
+
+    #[test]
+    fn serialize_mail() {
+        let mail = parse_mail(concat!(
+            "Subject: =?utf-8?Q?Caf=C3=A9?=\n",
+            "Content-Type: multipart/mixed; boundary=b\n\n",
+            "--b\n",
+            "Content-Type: text/plain; charset=utf-8\n",
+            "Content-Transfer-Encoding: base64\n\n",
+            "4oKs\n",
+            "--b--\n").as_bytes()).unwrap();
+        let json = serde_json::to_value(&mail).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "headers": [
+                {"key": "Subject", "value": "Caf\u{e9}"},
+                {"key": "Content-Type", "value": "multipart/mixed; boundary=b"},
+            ],
+            "ctype": {"mimetype": "multipart/mixed", "charset": null, "params": {"boundary": "b"}},
+            "body": "",
+            "subparts": [{
+                "headers": [
+                    {"key": "Content-Type", "value": "text/plain; charset=utf-8"},
+                    {"key": "Content-Transfer-Encoding", "value": "base64"},
+                ],
+                "ctype": {"mimetype": "text/plain", "charset": "utf-8",
+                          "params": {"charset": "utf-8"}},
+                "body": "\u{20ac}",
+                "subparts": [],
+            }],
+        }));
+
+        let mail = parse_mail(b"Content-Transfer-Encoding: x-bogus\n\nbody").unwrap();
+        assert!(serde_json::to_string(&mail).is_err());
+    }
+}
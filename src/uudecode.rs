@@ -0,0 +1,111 @@
+use {ErrorKind, MailParseError};
+
+fn uudecode_char(c: u8, ix: usize) -> Result<u8, MailParseError> {
+    if (b' '..=b'`').contains(&c) {
+        Ok((c - b' ') & 0x3f)
+    } else {
+        Err(MailParseError::Generic(ErrorKind::InvalidUuencodeCharacter, ix))
+    }
+}
+
+fn strip_cr(line: &[u8]) -> &[u8] {
+    match line.last() {
+        Some(&b'\r') => &line[..line.len() - 1],
+        _ => line,
+    }
+}
+
+/// Decode uuencoded data, i.e. data framed by a `begin <mode> <name>` line
+/// and an `end` line. Returns the filename from the begin line (if there was
+/// one) along with the decoded bytes. Any text before the begin line is
+/// ignored. A missing end line is tolerated, since it is usually the result
+/// of a truncated message and the data decoded so far is still useful.
+pub fn uudecode(data: &[u8]) -> Result<(Option<String>, Vec<u8>), MailParseError> {
+    // Pair each line up with the offset at which it starts, for error reporting
+    let mut ix_line = 0;
+    let mut lines = data.split(|c| *c == b'\n').map(|line| {
+        let ix = ix_line;
+        ix_line += line.len() + 1;
+        (ix, strip_cr(line))
+    });
+    let filename = loop {
+        match lines.next() {
+            None => return Err(MailParseError::Generic(ErrorKind::MissingUuencodeBegin, 0)),
+            Some((_, line)) if line.starts_with(b"begin ") => {
+                break line
+                    .splitn(3, |c| *c == b' ')
+                    .nth(2)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| String::from_utf8_lossy(name).into_owned());
+            }
+            Some(_) => continue,
+        }
+    };
+
+    let mut result = Vec::new();
+    for (ix, line) in lines {
+        if line == b"end" {
+            break;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let len = uudecode_char(line[0], ix)? as usize;
+        let mut decoded = Vec::with_capacity(len + 2);
+        // Some encoders strip trailing spaces from the lines, so missing
+        // characters at the end of a group are treated as zeroes.
+        for (ix_group, group) in line[1..].chunks(4).enumerate() {
+            let mut v = [0u8; 4];
+            for (i, c) in group.iter().enumerate() {
+                v[i] = uudecode_char(*c, ix + 1 + ix_group * 4 + i)?;
+            }
+            decoded.push((v[0] << 2) | (v[1] >> 4));
+            decoded.push((v[1] << 4) | (v[2] >> 2));
+            decoded.push((v[2] << 6) | v[3]);
+        }
+        if decoded.len() < len {
+            return Err(MailParseError::Generic(ErrorKind::TruncatedUuencodeLine, ix));
+        }
+        decoded.truncate(len);
+        result.extend_from_slice(&decoded);
+    }
+    Ok((filename, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_uuencoded() {
+        let (name, data) = uudecode(concat!(
+            "begin 644 cat.txt\n",
+            "#0V%T\n",
+            "`\n",
+            "end\n").as_bytes()).unwrap();
+        assert_eq!(name, Some("cat.txt".to_string()));
+        assert_eq!(data, b"Cat");
+
+        let (name, data) = uudecode(concat!(
+            "Some preamble\r\n",
+            "begin 600 hello world.txt\r\n",
+            "+:&5L;&\\@=V]R;&0`\r\n",
+            "`\r\n",
+            "end\r\n").as_bytes()).unwrap();
+        assert_eq!(name, Some("hello world.txt".to_string()));
+        assert_eq!(data, b"hello world");
+
+        match uudecode(b"no begin line here\n").unwrap_err() {
+            MailParseError::Generic(ErrorKind::MissingUuencodeBegin, 0) => (),
+            e => panic!("Unexpected error {:?}", e),
+        }
+        match uudecode(b"begin 644 x\n#0V\x01T\nend\n").unwrap_err() {
+            MailParseError::Generic(ErrorKind::InvalidUuencodeCharacter, 15) => (),
+            e => panic!("Unexpected error {:?}", e),
+        }
+        match uudecode(b"begin 644 x\nM0V%T\nend\n").unwrap_err() {
+            MailParseError::Generic(ErrorKind::TruncatedUuencodeLine, 12) => (),
+            e => panic!("Unexpected error {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use {
+    charset_encoding, is_unknown_charset, parse_mail, HeaderToken, MailParseError, ParsedMail,
+    TransferEncoding,
+};
+
+/// A problem with a message that doesn't prevent it from being parsed, but
+/// that may explain why (part of) it looks odd. These are reported by
+/// `parse_mail_with_warnings`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// The charset of a part is not recognized, so its body is decoded with
+    /// a fallback charset. The string holds the charset as declared. This is
+    /// not reported for an empty charset or a placeholder like "unknown", as
+    /// those are deliberately decoded as UTF-8.
+    UnknownCharset(String),
+    /// An RFC 2047 encoded-word in the value of a header could not be
+    /// decoded (e.g. because of an unknown charset or invalid base64), so it
+    /// is left as-is in the value. The strings hold the header key and the
+    /// encoded-word.
+    UndecodedEncodedWord(String, String),
+    /// A multipart part has no boundary parameter, so its body cannot be
+    /// split into parts.
+    MissingBoundary,
+    /// The boundary delimiter of a multipart part does not appear in its
+    /// body, so it has no parts. The string holds the boundary.
+    BoundaryNotFound(String),
+    /// A multipart part is missing its closing boundary delimiter, which
+    /// usually means the message was truncated. The string holds the boundary.
+    UnterminatedMultipart(String),
+    /// The Content-Transfer-Encoding of a part is not supported, so its body
+    /// cannot be decoded. The string holds the (lowercased) encoding.
+    UnsupportedTransferEncoding(String),
+    /// A line in a header block is terminated by a bare CR rather than a CRLF
+    /// or LF. The value is the byte offset of the CR in the input.
+    BareCarriageReturn(usize),
+}
+
+/// Parse a message, as with `parse_mail`, and also check it for problems
+/// that `parse_mail` silently works around. The warnings are returned in
+/// the order of the parts they apply to (depth-first), and in the order in
+/// which they appear within each part. Errors that prevent the message from
+/// being parsed at all are still returned as errors.
+///
+/// # Examples
+/// ```
+///     use mailparse::{parse_mail_with_warnings, Warning};
+///     let (mail, warnings) = parse_mail_with_warnings(concat!(
+///             "Subject: =?x-bogus?Q?hi?=\n",
+///             "Content-Type: multipart/mixed; boundary=b\n",
+///             "\n",
+///             "--b\n",
+///             "Content-Type: text/plain; charset=x-klingon\n",
+///             "\n",
+///             "Qapla'\n").as_bytes())
+///         .unwrap();
+///     assert_eq!(mail.subparts.len(), 1);
+///     assert_eq!(warnings, vec![
+///         Warning::UndecodedEncodedWord("Subject".to_string(), "=?x-bogus?Q?hi?=".to_string()),
+///         Warning::UnterminatedMultipart("b".to_string()),
+///         Warning::UnknownCharset("x-klingon".to_string()),
+///     ]);
+/// ```
+pub fn parse_mail_with_warnings(
+    raw_data: &[u8],
+) -> Result<(ParsedMail<'_>, Vec<Warning>), MailParseError> {
+    let mail = parse_mail(raw_data)?;
+    let mut warnings = Vec::new();
+    collect_warnings(raw_data, &mail, &mut warnings);
+    Ok((mail, warnings))
+}
+
+fn collect_warnings(raw_data: &[u8], part: &ParsedMail, warnings: &mut Vec<Warning>) {
+    // The header block runs from the first header up to the body
+    let ix_body = offset_in(raw_data, part.body);
+    if let Some(first) = part.headers.first() {
+        let ix_headers = offset_in(raw_data, first.key);
+        for ix in ix_headers..ix_body {
+            if raw_data[ix] == b'\r' && raw_data.get(ix + 1) != Some(&b'\n') {
+                warnings.push(Warning::BareCarriageReturn(ix));
+            }
+        }
+    }
+
+    for header in &part.headers {
+        let key = header.get_key().unwrap_or_default();
+        for token in header.value_tokens().unwrap_or_default() {
+            if let HeaderToken::Literal(text) = token {
+                for word in text.split_whitespace().filter_map(undecoded_word) {
+                    warnings.push(Warning::UndecodedEncodedWord(key.clone(), word.to_string()));
+                }
+            }
+        }
+    }
+
+    if let Ok(TransferEncoding::Other(enc)) = part.transfer_encoding() {
+        warnings.push(Warning::UnsupportedTransferEncoding(enc));
+    }
+    if part.ctype.mimetype.starts_with("multipart/") {
+        match part.ctype.params.get("boundary") {
+            None => warnings.push(Warning::MissingBoundary),
+            Some(boundary) if part.subparts.is_empty() => {
+                warnings.push(Warning::BoundaryNotFound(boundary.clone()))
+            }
+            Some(boundary) if part.is_truncated() => {
+                warnings.push(Warning::UnterminatedMultipart(boundary.clone()))
+            }
+            Some(_) => (),
+        }
+    } else if let Some(ref charset) = part.ctype.charset {
+        if charset_encoding(charset).is_none() && !is_unknown_charset(charset) {
+            warnings.push(Warning::UnknownCharset(charset.clone()));
+        }
+    }
+
+    for subpart in &part.subparts {
+        collect_warnings(raw_data, subpart, warnings);
+    }
+}
+
+/// Get the offset of a slice that borrows from `raw_data` within it.
+fn offset_in(raw_data: &[u8], slice: &[u8]) -> usize {
+    slice.as_ptr() as usize - raw_data.as_ptr() as usize
+}
+
+/// Check if a whitespace-separated word of literal header text is an
+/// encoded-word that was left undecoded, ignoring any surrounding quotes or
+/// comment parentheses, and if so return the encoded-word.
+fn undecoded_word(word: &str) -> Option<&str> {
+    let word = word.trim_matches(|c| matches!(c, '"' | '(' | ')' | '<' | '>'));
+    let is_encoded_word = word.len() > 4 &&
+        word.starts_with("=?") &&
+        word.ends_with("?=") &&
+        word[2..word.len() - 2].matches('?').count() == 2;
+    if is_encoded_word {
+        Some(word)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_warnings() {
+        let (_, warnings) = parse_mail_with_warnings(b"Subject: fine\r\n\r\nBody").unwrap();
+        assert!(warnings.is_empty());
+
+        let raw = concat!(
+            "Subject: \"=?utf-8?B?!!!?=\" =?utf-8?Q?ok?= =?bad\r",
+            "Content-Type: multipart/mixed; boundary=outer\r\n",
+            "\r\n",
+            "--outer\r\n",
+            "Content-Type: multipart/alternative\r\n",
+            "\r\n",
+            "--outer\r\n",
+            "Content-Type: multipart/related; boundary=missing\r\n",
+            "\r\n",
+            "no delimiter\r\n",
+            "--outer\r\n",
+            "Content-Transfer-Encoding: x-compressed\r\n",
+            "Content-Type: text/plain; charset=\"\"\r\n",
+            "\r\n",
+            "data\r\n",
+            "--outer--\r\n");
+        let (mail, warnings) = parse_mail_with_warnings(raw.as_bytes()).unwrap();
+        assert_eq!(mail.subparts.len(), 3);
+        assert_eq!(warnings, vec![
+            Warning::BareCarriageReturn(47),
+            Warning::UndecodedEncodedWord("Subject".to_string(), "=?utf-8?B?!!!?=".to_string()),
+            Warning::MissingBoundary,
+            Warning::BoundaryNotFound("missing".to_string()),
+            Warning::UnsupportedTransferEncoding("x-compressed".to_string()),
+        ]);
+        assert_eq!(raw.as_bytes()[47], b'\r');
+    }
+}